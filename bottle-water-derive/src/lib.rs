@@ -0,0 +1,229 @@
+// `#[derive(VertexLayout)]`, which writes out the `wgpu::VertexBufferLayout` of a vertex struct so we don't have to
+//
+// #[repr(C)]
+// #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+// #[step_mode(instance)] // Optional, one vertex per element of the buffer is the default
+// struct Particle {
+//     #[location(2)]
+//     position: [f32; 3],
+//     // Stored as 4 bytes, read as a `vec4` from 0 to 1 by the shader
+//     #[location(3)]
+//     #[normalized]
+//     color: [u8; 4],
+//     // Fields without a location still take up space in the buffer, they just aren't passed to the shader
+//     _padding: u32,
+// }
+//
+// Arrays of arrays like `[[f32; 4]; 4]` are matrices, they take up one location per column, starting at `location`
+// The trait that this implements, and the field types that it understands, are in `src/vertex.rs` of `bottle-water`
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Lit, Meta, NestedMeta, Type};
+
+#[proc_macro_derive(VertexLayout, attributes(location, normalized, step_mode))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+// One `wgpu::VertexAttribute`, matrix fields turn into several of these
+struct Attribute {
+    location: u32,
+    // Index of the field that this is (part of)
+    field: usize,
+    // Which column of the matrix this is, always 0 for fields that aren't matrices
+    column: usize,
+    // The type of this attribute, for matrices the type of a single column
+    ty: Type,
+    normalized: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    // Offsets can only be worked out when the fields are laid out in the order that they're declared
+    if !has_repr_c(&input.attrs) {
+        return Err(Error::new(
+            name.span(),
+            "VertexLayout needs #[repr(C)], otherwise the offsets of the fields aren't known",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "VertexLayout can't be derived for generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "VertexLayout can only be derived for structs",
+            ))
+        }
+    };
+    let step_mode = step_mode(&input.attrs)?;
+
+    let mut attributes = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let location = match location(&field.attrs)? {
+            Some(location) => location,
+            None => continue,
+        };
+        let normalized = field
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("normalized"));
+        // `[[f32; 4]; 4]` => 4 columns of `[f32; 4]`
+        let (columns, ty) = match &field.ty {
+            Type::Array(array) if matches!(*array.elem, Type::Array(_)) => {
+                (array_length(&array.len)?, (*array.elem).clone())
+            }
+            ty => (1, ty.clone()),
+        };
+        for column in 0..columns {
+            let attribute = Attribute {
+                location: location + column as u32,
+                field: index,
+                column,
+                ty: ty.clone(),
+                normalized,
+            };
+            if attributes
+                .iter()
+                .any(|other: &Attribute| other.location == attribute.location)
+            {
+                return Err(Error::new(
+                    field.span(),
+                    format!("more than one field uses location {}", attribute.location),
+                ));
+            }
+            attributes.push(attribute);
+        }
+    }
+
+    // `#[repr(C)]` puts every field at the first multiple of its alignment after the end of the previous one
+    let field_types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let offsets = (0..field_types.len())
+        .map(|i| format_ident!("OFFSET_{}", i))
+        .collect::<Vec<_>>();
+    let offset_consts = field_types.iter().enumerate().map(|(i, ty)| {
+        let offset = &offsets[i];
+        if i == 0 {
+            quote! { const #offset: u64 = 0; }
+        } else {
+            let (previous, previous_ty) = (&offsets[i - 1], field_types[i - 1]);
+            quote! {
+                const #offset: u64 = ::bottle_water::vertex::align_offset(
+                    #previous + ::std::mem::size_of::<#previous_ty>() as u64,
+                    ::std::mem::align_of::<#ty>() as u64,
+                );
+            }
+        }
+    });
+
+    let attributes = attributes.iter().map(|attribute| {
+        let Attribute {
+            location,
+            field,
+            column,
+            ty,
+            normalized,
+        } = attribute;
+        let offset = &offsets[*field];
+        // Using a type that doesn't have a vertex format becomes an error about these traits not being implemented
+        let format = if *normalized {
+            quote! { <#ty as ::bottle_water::vertex::NormalizedVertexAttribute>::FORMAT }
+        } else {
+            quote! { <#ty as ::bottle_water::vertex::VertexAttributeType>::FORMAT }
+        };
+        quote! {
+            ::wgpu::VertexAttribute {
+                offset: #offset + #column as u64 * ::std::mem::size_of::<#ty>() as u64,
+                shader_location: #location,
+                format: #format,
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl ::bottle_water::vertex::VertexLayout for #name {
+            const STEP_MODE: ::wgpu::InputStepMode = #step_mode;
+            const ATTRIBUTES: &'static [::wgpu::VertexAttribute] = {
+                #(#offset_consts)*
+                &[#(#attributes),*]
+            };
+        }
+    })
+}
+
+fn has_repr_c(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("repr"))
+        .filter_map(|attr| attr.parse_meta().ok())
+        .any(|meta| match meta {
+            Meta::List(list) => list.nested.iter().any(
+                |nested| matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("C")),
+            ),
+            _ => false,
+        })
+}
+
+// `#[step_mode(vertex)]` or `#[step_mode(instance)]` on the struct
+fn step_mode(attrs: &[syn::Attribute]) -> syn::Result<TokenStream2> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("step_mode")) {
+        Some(attr) => attr,
+        None => return Ok(quote! { ::wgpu::InputStepMode::Vertex }),
+    };
+    let error = || {
+        Error::new(
+            attr.span(),
+            "expected #[step_mode(vertex)] or #[step_mode(instance)]",
+        )
+    };
+    match attr.parse_meta()? {
+        Meta::List(list) if list.nested.len() == 1 => match &list.nested[0] {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("vertex") => {
+                Ok(quote! { ::wgpu::InputStepMode::Vertex })
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("instance") => {
+                Ok(quote! { ::wgpu::InputStepMode::Instance })
+            }
+            _ => Err(error()),
+        },
+        _ => Err(error()),
+    }
+}
+
+// `#[location(n)]` on a field, `None` if the field doesn't have one
+fn location(attrs: &[syn::Attribute]) -> syn::Result<Option<u32>> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("location")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    match attr.parse_meta()? {
+        Meta::List(list) if list.nested.len() == 1 => match &list.nested[0] {
+            NestedMeta::Lit(Lit::Int(location)) => location.base10_parse().map(Some),
+            _ => Err(Error::new(list.span(), "expected #[location(n)]")),
+        },
+        _ => Err(Error::new(attr.span(), "expected #[location(n)]")),
+    }
+}
+
+// The number of columns of a matrix field, which has to be written out as a number
+fn array_length(len: &Expr) -> syn::Result<usize> {
+    match len {
+        Expr::Lit(syn::ExprLit {
+            lit: Lit::Int(len), ..
+        }) => len.base10_parse(),
+        _ => Err(Error::new(
+            len.span(),
+            "the number of columns of a matrix has to be a number",
+        )),
+    }
+}
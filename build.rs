@@ -1,17 +1,54 @@
-use anyhow::*;
+use anyhow::{anyhow, bail, Context, Result};
 use glob::glob;
-use std::fs::{read_to_string, write};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{create_dir_all, read, read_to_string, remove_file, write};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// The same `#include` handling that the runtime compiler uses
+#[path = "src/includes.rs"]
+mod includes;
 
 struct ShaderData {
     src: String,
     src_path: PathBuf,
+    // Path relative to `src/`, always with `/`s, e.g. `shader.vert`
+    // This is the name that the shader gets in the `ShaderLibrary`
+    name: String,
+    // Where the SPIR-V (or the validated WGSL) goes
+    out_path: PathBuf,
+    language: Language,
+    // The `ShaderStage` variant in the generated code, and the function that runs for that stage
+    // Only known for WGSL once it's been validated, GLSL files are `main` unless the manifest says otherwise
+    entry_points: Vec<(&'static str, String)>,
+    // Extra copies of the shader compiled with different `#define`s, from the manifest
+    variants: Vec<Variant>,
+    // Every file that any of its variants or entry points `#include`d, relative to the crate root when it's inside of it
+    // Only known after compiling, hot reload watches these along with the source
+    includes: Vec<PathBuf>,
+}
+
+// One permutation of a shader
+struct Variant {
+    // Sorted by name, a define without a value has `""` as its value
+    defines: Vec<(String, String)>,
+    // e.g. `shader.frag[FLAT_COLOR]` or `shader.frag[MAX_LIGHTS=4]`, the name in the `ShaderLibrary`
+    name: String,
     spv_path: PathBuf,
-    kind: shaderc::ShaderKind,
+}
+
+enum Language {
+    // Compiled to SPIR-V with shaderc
+    Glsl(shaderc::ShaderKind),
+    // Only validated with naga, wgpu compiles it at run time
+    Wgsl,
 }
 
 impl ShaderData {
-    pub fn load(src_path: PathBuf) -> Result<Self> {
+    pub fn load(src_path: PathBuf, out_dir: &Path, manifest: &toml::Value) -> Result<Self> {
         println!("2");
         // Try to get the extension of the file
         let extension = src_path
@@ -24,10 +61,22 @@ impl ShaderData {
             // Again, return error with some context message if we have to
             .context("Extension cannot be converted to &str")?;
         // Get the kind of shader we're reading based off of the extension
-        let kind = match extension {
-            "vert" => shaderc::ShaderKind::Vertex,
-            "frag" => shaderc::ShaderKind::Fragment,
-            "comp" => shaderc::ShaderKind::Compute,
+        let (language, stage) = match extension {
+            "vert" => (Language::Glsl(shaderc::ShaderKind::Vertex), "Vertex"),
+            "frag" => (Language::Glsl(shaderc::ShaderKind::Fragment), "Fragment"),
+            "comp" => (Language::Glsl(shaderc::ShaderKind::Compute), "Compute"),
+            // wgpu can't run these yet, but they're still compiled so that they're checked and ready for when it can
+            "geom" => (Language::Glsl(shaderc::ShaderKind::Geometry), "Geometry"),
+            "tesc" => (
+                Language::Glsl(shaderc::ShaderKind::TessControl),
+                "TessControl",
+            ),
+            "tese" => (
+                Language::Glsl(shaderc::ShaderKind::TessEvaluation),
+                "TessEvaluation",
+            ),
+            // Which stages a WGSL file has only comes out of parsing it
+            "wgsl" => (Language::Wgsl, ""),
             // `bail!` is basically `.context()` in a macro
             // (somepath)`.display()` returns the path but safe for printing without unicode characters
             _ => bail!("Unsupported shader: {}", src_path.display()),
@@ -35,53 +84,119 @@ impl ShaderData {
 
         // Get the contents of the shader file ofc
         let src = read_to_string(src_path.clone())?;
-        // Set the file extension to name.(vert | frag | comp).spv
-        // `with_extension` replaces extension to the file
-        let spv_path = src_path.with_extension(format!("{}.spv", extension));
+        let name = src_path
+            .strip_prefix("src")
+            .context("Shader is not inside of src/")?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        // The compiled shaders go to `OUT_DIR` instead of next to the sources, so the source tree is never written to
+        // name.(vert | frag | comp | ...).spv or name.wgsl, keeping the same folders as in `src/`
+        let (out_path, entry_points) = match language {
+            Language::Glsl(_) => (
+                out_dir.join("shaders").join(format!("{}.spv", name)),
+                read_entry_points(manifest, &name)?
+                    .into_iter()
+                    .map(|entry_point| (stage, entry_point))
+                    .collect(),
+            ),
+            Language::Wgsl => {
+                if read_entry_points(manifest, &name)? != ["main"] {
+                    bail!(
+                        "{} is WGSL, its entry points come from the source instead of the manifest",
+                        name
+                    );
+                }
+                (out_dir.join("shaders").join(&name), Vec::new())
+            }
+        };
+
+        let variants = read_variants(manifest, &name)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, defines)| Variant {
+                name: variant_name(&name, &defines),
+                spv_path: out_dir
+                    .join("shaders")
+                    .join(format!("{}.variant{}.spv", name, i)),
+                defines,
+            })
+            .collect::<Vec<_>>();
+        if matches!(language, Language::Wgsl) && !variants.is_empty() {
+            bail!(
+                "{} is WGSL, which can't have variants since it has no #define",
+                name
+            );
+        }
+        if entry_points.len() > 1 && !variants.is_empty() {
+            bail!(
+                "{} has both variants and more than one entry point, which isn't supported",
+                name
+            );
+        }
 
         Ok(Self {
             src,
             src_path,
-            spv_path,
-            kind,
+            name,
+            out_path,
+            language,
+            entry_points,
+            variants,
+            includes: Vec::new(),
         })
     }
 }
 
 // Remember we have `main` return `Result<()>` so we're able to use the `?` operator to auto return errors
 fn main() -> Result<()> {
+    // Cargo gives every build script its own directory to write to
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+
+    // Extra settings for some of the shaders, like which variants to build
+    println!("cargo:rerun-if-changed={}", MANIFEST_PATH);
+    let manifest = read_to_string(MANIFEST_PATH)
+        .context("Unable to read the shader manifest")?
+        .parse::<toml::Value>()
+        .context("Unable to parse the shader manifest")?;
+
     // Collect all shaders recursively within /src/
     // Remember glob comes from `glob` pkg
     let mut shader_paths = [
         glob("./src/**/*.vert")?,
         glob("./src/**/*.frag")?,
         glob("./src/**/*.comp")?,
+        glob("./src/**/*.geom")?,
+        glob("./src/**/*.tesc")?,
+        glob("./src/**/*.tese")?,
+        glob("./src/**/*.wgsl")?,
     ];
 
     // This could be parallelized
     // TODO fkin do not know closures ffs ffsfsfsfsf
     // Basically looks like we're iterating over each path, and for each path creating `shaderData`
-    let shaders = shader_paths
+    let mut shaders = shader_paths
         .iter_mut()
         .flatten()
-        .map(|glob_result| ShaderData::load(glob_result?))
+        .map(|glob_result| ShaderData::load(glob_result?, &out_dir, &manifest))
         // TODO have no idea what these `collect` statements do lol
         .collect::<Vec<Result<_>>>()
         .into_iter()
         .collect::<Result<Vec<_>>>()?;
+    check_manifest(&manifest, &shaders)?;
+
+    // `#include`d files are looked up in these directories (see `src/includes.rs`)
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?);
+    println!("cargo:rerun-if-env-changed={}", includes::INCLUDE_PATH_VAR);
+    let include_dirs = includes::include_dirs(&manifest_dir);
 
-    // Init new shaderc compiler to compile the string
-    // Compiler compiles GLSL into SPIR-V modules
-    // let mut compiler = shaderc::Compiler::new().unwrap();
-    // So compile both the vertex and fragment shader form GLSL into spirv
-    let mut compiler = shaderc::Compiler::new().context("Unable to create shader compiler")?;
-
-    // This can't be parallelized. The [shaderc::Compiler] is not
-    // thread safe. Also, it creates a lot of resources. You could
-    // spawn multiple processes to handle this, but it would probably
-    // be better just to only compile shaders that have been changed
-    // recently.
-    for shader in shaders {
+    // Every error and warning from every shader, so that one broken shader doesn't hide the problems in the others
+    let mut diagnostics = Vec::new();
+
+    // Every GLSL shader and variant that has to go through shaderc
+    let mut jobs = Vec::new();
+    for shader in &mut shaders {
         // This tells cargo to rerun this script if something in /src/ changes.
         // TODO whoa cool wtf
         println!(
@@ -89,21 +204,975 @@ fn main() -> Result<()> {
             shader.src_path.as_os_str().to_str().unwrap()
         );
 
-        // Now we can basically just spread our `ShaderData` here to compile into SPIRV
-        // note the `?` operator at the very end lol
-        let compiled = compiler.compile_into_spirv(
-            // Content of shader
-            &shader.src,
-            // Kind of shader
-            shader.kind,
-            // Path of file as `&str`
-            &shader.src_path.to_str().unwrap(),
-            "main",
-            None,
-        )?;
-        // Write file to the designated `spv` bath as binary
-        write(shader.spv_path, compiled.as_binary_u8())?;
+        let kind = match shader.language {
+            Language::Glsl(kind) => kind,
+            // Only validated, then copied over as it is
+            Language::Wgsl => {
+                match validate_wgsl(&shader.src, &shader.src_path) {
+                    Ok(entry_points) => shader.entry_points = entry_points,
+                    Err(diagnostic) => {
+                        diagnostics.push(diagnostic);
+                        continue;
+                    }
+                }
+                create_dir_all(shader.out_path.parent().unwrap())?;
+                write(&shader.out_path, &shader.src)?;
+                continue;
+            }
+        };
+
+        // The shader as it is, then every variant of it, all with the first entry point
+        let permutations = std::iter::once((None, Vec::new(), shader.out_path.clone())).chain(
+            shader.variants.iter().map(|variant| {
+                (
+                    Some(format!("the variant {}", variant.name)),
+                    variant.defines.clone(),
+                    variant.spv_path.clone(),
+                )
+            }),
+        );
+        for (compiling, defines, spv_path) in permutations {
+            jobs.push(Job {
+                src: shader.src.clone(),
+                src_path: shader.src_path.clone(),
+                kind,
+                defines,
+                spv_path,
+                entry_point: shader.entry_points[0].1.clone(),
+                compiling,
+            });
+        }
+        // A SPIR-V module from shaderc only ever has one entry point, so the others each get a module of their own
+        for (_, entry_point) in shader.entry_points.iter().skip(1) {
+            jobs.push(Job {
+                src: shader.src.clone(),
+                src_path: shader.src_path.clone(),
+                kind,
+                defines: Vec::new(),
+                spv_path: entry_point_spv_path(&shader.out_path, entry_point),
+                entry_point: entry_point.clone(),
+                compiling: Some(format!("the entry point `{}`", entry_point)),
+            });
+        }
+    }
+
+    // A [shaderc::Compiler] isn't thread safe and is pretty heavy, so by default there's only one of them.
+    // Big shader trees can set `SHADER_COMPILE_JOBS` to compile on that many threads, each with its own compiler.
+    // Either way, shaders that haven't changed since the last build come out of the cache instead (see `compile`)
+    println!("cargo:rerun-if-env-changed={}", COMPILE_JOBS_VAR);
+    let threads = match std::env::var(COMPILE_JOBS_VAR) {
+        Ok(threads) => threads
+            .parse::<usize>()
+            .ok()
+            .filter(|threads| *threads > 0)
+            .with_context(|| format!("{} has to be a number above 0", COMPILE_JOBS_VAR))?,
+        Err(_) => 1,
+    };
+    let jobs = Arc::new(jobs);
+    let results = compile_all(&jobs, &include_dirs, threads)?;
+
+    for (job, result) in jobs.iter().zip(results) {
+        // Shared code has to be watched as well, or editing it wouldn't rebuild the shaders that include it
+        for include in &result.includes {
+            println!("cargo:rerun-if-changed={}", include.display());
+        }
+        let shader = shaders
+            .iter_mut()
+            .find(|shader| shader.src_path == job.src_path)
+            .unwrap();
+        for include in &result.includes {
+            let include = include
+                .strip_prefix(&manifest_dir)
+                .unwrap_or(include)
+                .to_path_buf();
+            if !shader.includes.contains(&include) {
+                shader.includes.push(include);
+            }
+        }
+        let (severity, messages) = match &result.outcome {
+            Ok(warnings) => (Severity::Warning, warnings),
+            Err(errors) => (Severity::Error, errors),
+        };
+        for mut diagnostic in parse_shaderc_messages(severity, messages, &job.src_path) {
+            // A mistake in an `#include`d file shows up in everything that includes it, only keep the first
+            if diagnostics.iter().any(|other| diagnostic.same_as(other)) {
+                continue;
+            }
+            // Variants and entry points can fail where the shader itself doesn't, so say which one it was
+            diagnostic.compiling = job.compiling.clone();
+            diagnostics.push(diagnostic);
+        }
     }
 
+    // Warnings don't stop the build, cargo shows them as long as they're one line each
+    for diagnostic in &diagnostics {
+        if let Severity::Warning = diagnostic.severity {
+            println!("cargo:warning={}", diagnostic.summary());
+        }
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| matches!(diagnostic.severity, Severity::Error))
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        bail!(
+            "{} shader error{}\n\n{}",
+            errors.len(),
+            if errors.len() == 1 { "" } else { "s" },
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    // Then a Rust module that embeds every compiled shader, `src/shader.rs` `include!`s it
+    write(out_dir.join("shaders.rs"), generate_registry(&shaders)?)?;
+
     Ok(())
 }
+
+const MANIFEST_PATH: &str = "src/shaders/manifest.toml";
+
+// Bump this whenever the way that shaders get compiled changes, so that nothing stale comes out of the cache
+const CACHE_VERSION: u32 = 1;
+// How many shaders get compiled at once, 1 unless it's set
+const COMPILE_JOBS_VAR: &str = "SHADER_COMPILE_JOBS";
+
+// One GLSL shader or variant to compile
+// It owns everything that it needs, so it can be handed to another thread
+struct Job {
+    src: String,
+    src_path: PathBuf,
+    kind: shaderc::ShaderKind,
+    defines: Vec<(String, String)>,
+    spv_path: PathBuf,
+    // The function that becomes the SPIR-V entry point, it's renamed to `main` while compiling since GLSL needs that
+    entry_point: String,
+    // Which variant or entry point this is, only used for the diagnostics
+    compiling: Option<String>,
+}
+
+struct JobResult {
+    // Every file that got `#include`d, so cargo can watch them
+    includes: Vec<PathBuf>,
+    // The warnings if it compiled, the errors if it didn't, still in shaderc's format
+    outcome: Result<String, String>,
+}
+
+impl Job {
+    // Everything that the compiled SPIR-V depends on, other than the contents of the `#include`d files
+    fn key(&self, include_dirs: &[PathBuf]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        CACHE_VERSION.hash(&mut hasher);
+        self.src_path.hash(&mut hasher);
+        self.src.hash(&mut hasher);
+        // `ShaderKind` doesn't implement `Hash`
+        format!("{:?}", self.kind).hash(&mut hasher);
+        // Already sorted, so the order that they're written in the manifest doesn't matter
+        self.defines.hash(&mut hasher);
+        include_dirs.hash(&mut hasher);
+        self.entry_point.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // e.g. `shader.vert.spv.cache`, next to the SPIR-V in `OUT_DIR`
+    fn cache_path(&self) -> PathBuf {
+        let mut path = self.spv_path.clone().into_os_string();
+        path.push(".cache");
+        PathBuf::from(path)
+    }
+}
+
+// Runs every job on `threads` threads, each with its own compiler
+// The results come back in the same order as the jobs, so the diagnostics don't depend on which thread was faster
+fn compile_all(
+    jobs: &Arc<Vec<Job>>,
+    include_dirs: &[PathBuf],
+    threads: usize,
+) -> Result<Vec<JobResult>> {
+    // Every thread takes the next job that nobody has started yet
+    let next = Arc::new(AtomicUsize::new(0));
+    let workers = (0..threads.min(jobs.len()))
+        .map(|_| {
+            let (jobs, next, include_dirs) = (jobs.clone(), next.clone(), include_dirs.to_vec());
+            std::thread::spawn(move || -> Result<Vec<(usize, JobResult)>> {
+                let mut compiler =
+                    shaderc::Compiler::new().context("Unable to create shader compiler")?;
+                let mut results = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let job = match jobs.get(index) {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    results.push((index, compile(&mut compiler, job, &include_dirs)?));
+                }
+                Ok(results)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::with_capacity(jobs.len());
+    for worker in workers {
+        let worker_results = worker
+            .join()
+            .map_err(|_| anyhow!("A shader compiler thread panicked"))??;
+        results.extend(worker_results);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+// Compiles one job, unless the cache says that the SPIR-V from last time is still up to date
+fn compile(
+    compiler: &mut shaderc::Compiler,
+    job: &Job,
+    include_dirs: &[PathBuf],
+) -> Result<JobResult> {
+    let key = job.key(include_dirs);
+    if let Some(cached) = read_cache(&job.cache_path(), key, &job.spv_path) {
+        return Ok(cached);
+    }
+
+    let included = RefCell::new(Vec::new());
+    // `CompileOptions::clone` doesn't keep the include callback, so every compile gets fresh options
+    let mut options =
+        shaderc::CompileOptions::new().context("Unable to create shader compile options")?;
+    includes::set_include_callback(&mut options, include_dirs.to_vec(), |path| {
+        included.borrow_mut().push(path.to_path_buf())
+    });
+    for (name, value) in &job.defines {
+        options.add_macro_definition(name, Some(value));
+    }
+    // `void blur_x()` => `void main()`, since glslang only ever compiles `main` out of GLSL
+    // It's still called `main` in the SPIR-V, shaderc only uses the entry point name for HLSL
+    if job.entry_point != "main" {
+        options.add_macro_definition(&job.entry_point, Some("main"));
+    }
+
+    // Now we can basically just spread our `Job` here to compile into SPIRV
+    let result = compiler.compile_into_spirv(
+        // Content of shader
+        &job.src,
+        // Kind of shader
+        job.kind,
+        // Path of file as `&str`
+        job.src_path.to_str().unwrap(),
+        "main",
+        Some(&options),
+    );
+    // The callback borrows `included`
+    drop(options);
+    let includes = included.into_inner();
+
+    let outcome = match result {
+        Ok(compiled) => {
+            let warnings = if compiled.get_num_warnings() > 0 {
+                compiled.get_warning_messages()
+            } else {
+                String::new()
+            };
+            // Write file to the designated `spv` bath as binary
+            create_dir_all(job.spv_path.parent().unwrap())?;
+            write(&job.spv_path, compiled.as_binary_u8())?;
+            write_cache(&job.cache_path(), key, &includes, &warnings)?;
+            Ok(warnings)
+        }
+        Err(shaderc::Error::CompilationError(_, messages)) => {
+            // Failures aren't cached, the errors have to show up every time until they're fixed
+            let _ = remove_file(job.cache_path());
+            Err(messages)
+        }
+        // Not a problem with the shader itself, e.g. shaderc being broken
+        Err(e) => return Err(e.into()),
+    };
+    Ok(JobResult { includes, outcome })
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    read(path).ok()?.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// The cache is a small text file:
+//
+// key <hash of the job>
+// include <hash of the contents> <path>
+// warning <one line of the warnings>
+//
+// The included files are hashed as well, since editing one changes the output without changing the job
+fn write_cache(cache_path: &Path, key: u64, includes: &[PathBuf], warnings: &str) -> Result<()> {
+    let mut cache = format!("key {:016x}\n", key);
+    for include in includes {
+        if let Some(hash) = hash_file(include) {
+            cache += &format!("include {:016x} {}\n", hash, include.display());
+        }
+    }
+    for line in warnings.lines() {
+        cache += &format!("warning {}\n", line);
+    }
+    write(cache_path, cache).context("Unable to write the shader cache")
+}
+
+// What the last build compiled, as long as nothing that it depends on has changed since
+fn read_cache(cache_path: &Path, key: u64, spv_path: &Path) -> Option<JobResult> {
+    let cache = read_to_string(cache_path).ok()?;
+    let mut lines = cache.lines();
+    if lines.next()? != format!("key {:016x}", key) || !spv_path.is_file() {
+        return None;
+    }
+    let mut includes = Vec::new();
+    let mut warnings = String::new();
+    for line in lines {
+        if let Some(include) = line.strip_prefix("include ") {
+            let mut parts = include.splitn(2, ' ');
+            let (hash, path) = (parts.next()?, PathBuf::from(parts.next()?));
+            if format!("{:016x}", hash_file(&path)?) != hash {
+                return None;
+            }
+            includes.push(path);
+        } else if let Some(warning) = line.strip_prefix("warning ") {
+            warnings += warning;
+            warnings.push('\n');
+        } else {
+            return None;
+        }
+    }
+    Some(JobResult {
+        includes,
+        outcome: Ok(warnings),
+    })
+}
+
+// Parses and validates a WGSL shader with naga, the same way wgpu will when it gets loaded
+// Returns its entry points, so the mistakes show up as build errors instead of when the program runs
+fn validate_wgsl(src: &str, src_path: &Path) -> Result<Vec<(&'static str, String)>, Diagnostic> {
+    let error = |line, column, message| Diagnostic {
+        severity: Severity::Error,
+        path: src_path.to_path_buf(),
+        line,
+        column,
+        message,
+        compiling: None,
+    };
+    let module = naga::front::wgsl::parse_str(src).map_err(|e| {
+        let message = e.error.to_string();
+        // A line of 0 means the file ended with something still open
+        let line = Some(e.line).filter(|line| *line > 0);
+        // naga's `pos` is just past the token that it choked on, move back to its start if it's in the message
+        let column = line.map(|line| {
+            let before = src
+                .lines()
+                .nth(line - 1)
+                .and_then(|source_line| source_line.get(..e.pos))
+                .unwrap_or_default();
+            match quoted_token(&message) {
+                Some(token) if before.ends_with(token) => e.pos - token.len() + 1,
+                _ => e.pos + 1,
+            }
+        });
+        error(line, column, message)
+    })?;
+    naga::proc::Validator::new()
+        .validate(&module)
+        .map_err(|e| error(None, None, format!("invalid WGSL: {:?}", e)))?;
+
+    let mut entry_points = module
+        .entry_points
+        .keys()
+        .map(|(stage, name)| {
+            let stage = match stage {
+                naga::ShaderStage::Vertex => "Vertex",
+                naga::ShaderStage::Fragment => "Fragment",
+                naga::ShaderStage::Compute => "Compute",
+            };
+            (stage, name.clone())
+        })
+        .collect::<Vec<_>>();
+    // The map's order is random, but the generated code shouldn't change between builds
+    entry_points.sort();
+    for pair in entry_points.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            return Err(error(
+                None,
+                None,
+                format!(
+                    "more than one {} entry point, which isn't supported yet",
+                    pair[0].0.to_lowercase()
+                ),
+            ));
+        }
+    }
+    if entry_points.is_empty() {
+        return Err(error(None, None, "no entry points".to_string()));
+    }
+    Ok(entry_points)
+}
+
+#[derive(Clone, Copy)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+// One error or warning, pointing at where in the source it is
+struct Diagnostic {
+    severity: Severity,
+    // Not always the shader that was being compiled, it could be in a file that it `#include`s
+    path: PathBuf,
+    // Both start at 1, `None` if the compiler didn't say
+    line: Option<usize>,
+    column: Option<usize>,
+    message: String,
+    // Which variant or entry point was being compiled, if the shader has more than one
+    // e.g. `the variant shader.frag[FLAT_COLOR]`
+    compiling: Option<String>,
+}
+
+impl Diagnostic {
+    // Whether both are the same problem in the same place, even if they came from compiling different shaders
+    fn same_as(&self, other: &Diagnostic) -> bool {
+        self.path == other.path
+            && self.line == other.line
+            && self.column == other.column
+            && self.message == other.message
+    }
+
+    // `src/shader.frag:12:5`, as much of it as is known
+    fn location(&self) -> String {
+        let mut location = self.path.display().to_string();
+        if let Some(line) = self.line {
+            location.push_str(&format!(":{}", line));
+            if let Some(column) = self.column {
+                location.push_str(&format!(":{}", column));
+            }
+        }
+        location
+    }
+
+    // `src/shader.frag:12:5: 'foo' : undeclared identifier`, for `cargo:warning` which only shows single lines
+    fn summary(&self) -> String {
+        match &self.compiling {
+            Some(compiling) => format!(
+                "{}: {} (while compiling {})",
+                self.location(),
+                self.message,
+                compiling
+            ),
+            None => format!("{}: {}", self.location(), self.message),
+        }
+    }
+}
+
+// Looks like rustc's errors, with the line of source that the error is on
+//
+// error: 'foo' : undeclared identifier
+//   --> src/shader.frag:12:13
+//    |
+// 12 |   f_color = foo;
+//    |             ^^^
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(f, "{}: {}", severity, self.message)?;
+        // Wide enough for the line number, so that the `|`s line up under it
+        let gutter = " ".repeat(self.line.map_or(1, |line| line.to_string().len()));
+        writeln!(f, "{}--> {}", gutter, self.location())?;
+
+        // The file is read again rather than kept around, since it could be any file that got `#include`d
+        let source_line = self.line.and_then(|line| {
+            read_to_string(&self.path)
+                .ok()
+                .and_then(|source| source.lines().nth(line - 1).map(str::to_string))
+        });
+        if let (Some(line), Some(source_line)) = (self.line, source_line) {
+            writeln!(f, "{} |", gutter)?;
+            writeln!(f, "{} | {}", line, source_line)?;
+            if let Some(column) = self.column {
+                // Underline the token that the message is about if it's quoted in there, otherwise just point at the column
+                let width = quoted_token(&self.message)
+                    .filter(|token| matches!(source_line.get(column - 1..), Some(rest) if rest.starts_with(token)))
+                    .map_or(1, str::len);
+                writeln!(
+                    f,
+                    "{} | {}{}",
+                    gutter,
+                    " ".repeat(column - 1),
+                    "^".repeat(width)
+                )?;
+            }
+        }
+        if let Some(compiling) = &self.compiling {
+            writeln!(f, "{} = note: while compiling {}", gutter, compiling)?;
+        }
+        Ok(())
+    }
+}
+
+// The compilers quote what they're complaining about, glslang as `'foo' : undeclared identifier` and naga as
+// ``unknown type: `foo` ``
+fn quoted_token(message: &str) -> Option<&str> {
+    let start = message.find(&['\'', '`'][..])?;
+    let quote = message[start..].chars().next()?;
+    let length = message[start + 1..].find(quote)?;
+    Some(&message[start + 1..start + 1 + length]).filter(|token| !token.is_empty())
+}
+
+// shaderc gives back one message per line, like `src/shader.frag:12: error: 'foo' : undeclared identifier`
+// The file is whichever one the error is in, which can be an `#include`d file instead of `src_path`
+fn parse_shaderc_messages(severity: Severity, messages: &str, src_path: &Path) -> Vec<Diagnostic> {
+    let marker = match severity {
+        Severity::Error => ": error: ",
+        Severity::Warning => ": warning: ",
+    };
+    let mut diagnostics = Vec::new();
+    for message in messages
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        // `1 error generated.` and the like, which don't tell us anything new
+        if message.ends_with(" generated.") {
+            continue;
+        }
+        let (path, line, message) = match message.find(marker) {
+            Some(index) => {
+                let (location, message) = (&message[..index], &message[index + marker.len()..]);
+                // Paths can have `:`s in them too (`C:\`), so the line number is whatever comes after the last one
+                match location.rfind(':') {
+                    Some(colon) if location[colon + 1..].parse::<usize>().is_ok() => (
+                        PathBuf::from(&location[..colon]),
+                        location[colon + 1..].parse().ok(),
+                        message,
+                    ),
+                    _ => (PathBuf::from(location), None, message),
+                }
+            }
+            None => (src_path.to_path_buf(), None, message),
+        };
+        // glslang doesn't give columns, but it usually quotes the token that it's complaining about, so look for it
+        let column = line.and_then(|line| {
+            let token = quoted_token(message)?;
+            let source = read_to_string(&path).ok()?;
+            let source_line = source.lines().nth(line - 1)?;
+            source_line.find(token).map(|column| column + 1)
+        });
+        diagnostics.push(Diagnostic {
+            severity,
+            path,
+            line,
+            column,
+            message: message.to_string(),
+            compiling: None,
+        });
+    }
+    diagnostics
+}
+
+// The define sets of every variant of `name` in the manifest, each one sorted by define name
+//
+// ["shader.frag"]
+// variants = [{ FLAT_COLOR = true }, { MAX_LIGHTS = 4 }]
+//
+// `true` defines the macro without a value, `false` leaves it out, numbers and strings become its value
+fn read_variants(manifest: &toml::Value, name: &str) -> Result<Vec<Vec<(String, String)>>> {
+    let variants = match manifest.get(name).and_then(|table| table.get("variants")) {
+        Some(variants) => variants
+            .as_array()
+            .with_context(|| format!("`variants` of {} has to be an array", name))?,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut define_sets = Vec::new();
+    for variant in variants {
+        let table = variant
+            .as_table()
+            .with_context(|| format!("Every variant of {} has to be a table of defines", name))?;
+        let mut defines = Vec::new();
+        for (define, value) in table {
+            if !define
+                .chars()
+                .all(|c| c == '_' || c.is_ascii_alphanumeric())
+                || define.starts_with(|c: char| c.is_ascii_digit())
+            {
+                bail!(
+                    "`{}` in a variant of {} isn't a valid macro name",
+                    define,
+                    name
+                );
+            }
+            let value = match value {
+                toml::Value::Boolean(true) => String::new(),
+                toml::Value::Boolean(false) => continue,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::String(value) => value.clone(),
+                _ => bail!(
+                    "`{}` in a variant of {} has to be a bool, number or string",
+                    define,
+                    name
+                ),
+            };
+            defines.push((define.clone(), value));
+        }
+        // Toml tables are already sorted, but the lookup at run time relies on it so make sure
+        defines.sort();
+        if define_sets.contains(&defines) {
+            bail!("{} has the same variant twice: {:?}", name, defines);
+        }
+        define_sets.push(defines);
+    }
+    Ok(define_sets)
+}
+
+// The entry points of a GLSL shader in the manifest, `main` if it doesn't have any
+//
+// ["post/blur.frag"]
+// entry_points = ["blur_x", "blur_y"]
+//
+// Every one of them is a function in the shader, which gets compiled as if it was `main`
+fn read_entry_points(manifest: &toml::Value, name: &str) -> Result<Vec<String>> {
+    let entry_points = match manifest
+        .get(name)
+        .and_then(|table| table.get("entry_points"))
+    {
+        Some(entry_points) => entry_points
+            .as_array()
+            .with_context(|| format!("`entry_points` of {} has to be an array", name))?,
+        None => return Ok(vec!["main".to_string()]),
+    };
+
+    let mut names = Vec::new();
+    for entry_point in entry_points {
+        let entry_point = entry_point
+            .as_str()
+            .with_context(|| format!("Every entry point of {} has to be a string", name))?;
+        if entry_point.is_empty()
+            || !entry_point
+                .chars()
+                .all(|c| c == '_' || c.is_ascii_alphanumeric())
+            || entry_point.starts_with(|c: char| c.is_ascii_digit())
+        {
+            bail!(
+                "`{}` in the entry points of {} isn't a valid function name",
+                entry_point,
+                name
+            );
+        }
+        if names.iter().any(|other| other == entry_point) {
+            bail!("{} has the entry point {} twice", name, entry_point);
+        }
+        names.push(entry_point.to_string());
+    }
+    if names.is_empty() {
+        bail!("{} needs at least one entry point", name);
+    }
+    if names.len() > 1 && names.iter().any(|entry_point| entry_point == "main") {
+        bail!(
+            "{} can't have `main` along with other entry points, they get renamed to `main` when they're compiled",
+            name
+        );
+    }
+    Ok(names)
+}
+
+// `shaders/post/blur.frag.spv` + `blur_y` => `shaders/post/blur.frag.blur_y.spv`
+fn entry_point_spv_path(out_path: &Path, entry_point: &str) -> PathBuf {
+    out_path.with_extension(format!("{}.spv", entry_point))
+}
+
+// Catches typos in the manifest, which would otherwise just be ignored
+fn check_manifest(manifest: &toml::Value, shaders: &[ShaderData]) -> Result<()> {
+    let table = manifest
+        .as_table()
+        .context("The shader manifest has to be a table")?;
+    for name in table.keys() {
+        if !shaders.iter().any(|shader| &shader.name == name) {
+            bail!(
+                "{} mentions {}, which isn't a shader in src/",
+                MANIFEST_PATH,
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+// `shader.frag` + `FLAT_COLOR`, `MAX_LIGHTS=4` => `shader.frag[FLAT_COLOR,MAX_LIGHTS=4]`
+fn variant_name(name: &str, defines: &[(String, String)]) -> String {
+    let defines = defines
+        .iter()
+        .map(|(define, value)| {
+            if value.is_empty() {
+                define.clone()
+            } else {
+                format!("{}={}", define, value)
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{}[{}]", name, defines.join(","))
+}
+
+// Turns a shader name into an enum variant, `shader2.vert` => `Shader2Vert`, `post/blur_x.frag` => `PostBlurXFrag`
+fn enum_variant_name(name: &str) -> String {
+    let mut variant = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            variant.push(first.to_ascii_uppercase());
+            variant.extend(chars);
+        }
+    }
+    // Identifiers can't start with a number
+    if variant.starts_with(|c: char| c.is_ascii_digit()) {
+        variant.insert(0, '_');
+    }
+    variant
+}
+
+// Writes out the `BuiltinShader` enum, with one variant per shader
+// Code that uses e.g. `BuiltinShader::ShaderVert` stops compiling as soon as `shader.vert` is renamed or deleted
+fn generate_registry(shaders: &[ShaderData]) -> Result<String> {
+    let variants = shaders
+        .iter()
+        .map(|shader| enum_variant_name(&shader.name))
+        .collect::<Vec<_>>();
+    for (i, variant) in variants.iter().enumerate() {
+        if let Some(j) = variants[..i].iter().position(|other| other == variant) {
+            bail!(
+                "{} and {} would both be called BuiltinShader::{}, rename one of them",
+                shaders[j].src_path.display(),
+                shaders[i].src_path.display(),
+                variant
+            );
+        }
+    }
+
+    // `{:?}` formatting gives us properly escaped string literals
+    let mut all = String::new();
+    let mut names = String::new();
+    let mut stages = String::new();
+    let mut entry_points = String::new();
+    let mut module_entry_points = String::new();
+    let mut all_entry_points = String::new();
+    let mut entry_point_modules = String::new();
+    let mut languages = String::new();
+    let mut sources = String::new();
+    let mut includes = String::new();
+    let mut permutations = String::new();
+    for (shader, variant) in shaders.iter().zip(&variants) {
+        for permutation in &shader.variants {
+            permutations.push_str(&format!(
+                "    ShaderVariant {{
+        shader: BuiltinShader::{},
+        name: {:?},
+        defines: &{:?},
+        spirv: include_bytes!({:?}),
+    }},\n",
+                variant, permutation.name, permutation.defines, permutation.spv_path
+            ));
+        }
+        all.push_str(&format!("        BuiltinShader::{},\n", variant));
+        names.push_str(&format!(
+            "            BuiltinShader::{} => {:?},\n",
+            variant, shader.name
+        ));
+        // A GLSL file with several entry points still only has the one stage
+        let mut shader_stages = shader
+            .entry_points
+            .iter()
+            .map(|(stage, _)| *stage)
+            .collect::<Vec<_>>();
+        shader_stages.dedup();
+        stages.push_str(&format!(
+            "            BuiltinShader::{} => &[{}],\n",
+            variant,
+            shader_stages
+                .iter()
+                .map(|stage| format!("ShaderStage::{}", stage))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        for stage in &shader_stages {
+            let (_, entry_point) = shader
+                .entry_points
+                .iter()
+                .find(|(other, _)| other == stage)
+                .unwrap();
+            entry_points.push_str(&format!(
+                "            (BuiltinShader::{}, ShaderStage::{}) => Some({:?}),\n",
+                variant, stage, entry_point
+            ));
+            // glslang calls the entry point of every GLSL module `main`, whatever the function is called
+            let module_entry_point = match shader.language {
+                Language::Glsl(_) => "GLSL_ENTRY_POINT".to_string(),
+                Language::Wgsl => format!("{:?}", entry_point),
+            };
+            module_entry_points.push_str(&format!(
+                "            (BuiltinShader::{}, ShaderStage::{}) => Some({}),\n",
+                variant, stage, module_entry_point
+            ));
+        }
+        all_entry_points.push_str(&format!(
+            "            BuiltinShader::{} => &[{}],\n",
+            variant,
+            shader
+                .entry_points
+                .iter()
+                .map(|(stage, entry_point)| format!("(ShaderStage::{}, {:?})", stage, entry_point))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if matches!(shader.language, Language::Glsl(_)) && shader.entry_points.len() > 1 {
+            for (i, (stage, entry_point)) in shader.entry_points.iter().enumerate() {
+                // The first entry point is in the shader's own module
+                let spv_path = if i == 0 {
+                    shader.out_path.clone()
+                } else {
+                    entry_point_spv_path(&shader.out_path, entry_point)
+                };
+                entry_point_modules.push_str(&format!(
+                    "    ShaderEntryPoint {{
+        shader: BuiltinShader::{},
+        stage: ShaderStage::{},
+        name: {:?},
+        module: {:?},
+        module_entry_point: GLSL_ENTRY_POINT,
+        spirv: include_bytes!({:?}),
+    }},\n",
+                    variant,
+                    stage,
+                    entry_point,
+                    format!("{}:{}", shader.name, entry_point),
+                    spv_path
+                ));
+            }
+        }
+        let (language, source) = match shader.language {
+            Language::Glsl(_) => (
+                "Glsl",
+                format!(
+                    "wgpu::util::make_spirv(include_bytes!({:?}))",
+                    shader.out_path
+                ),
+            ),
+            Language::Wgsl => (
+                "Wgsl",
+                format!(
+                    "wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!({:?})))",
+                    shader.out_path
+                ),
+            ),
+        };
+        languages.push_str(&format!(
+            "            BuiltinShader::{} => ShaderLanguage::{},\n",
+            variant, language
+        ));
+        sources.push_str(&format!(
+            "            BuiltinShader::{} => {},\n",
+            variant, source
+        ));
+        includes.push_str(&format!(
+            "            BuiltinShader::{} => &{:?},\n",
+            variant,
+            shader
+                .includes
+                .iter()
+                .map(|include| include.to_string_lossy())
+                .collect::<Vec<_>>()
+        ));
+    }
+
+    Ok(format!(
+        "// Generated by `build.rs`, every shader in `src/`
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinShader {{
+{variants}}}
+
+impl BuiltinShader {{
+    pub const ALL: &'static [BuiltinShader] = &[
+{all}    ];
+
+    // Path relative to `src/`, also the name in the `ShaderLibrary`
+    pub const fn name(self) -> &'static str {{
+        match self {{
+{names}        }}
+    }}
+
+    // Every stage that the shader has an entry point for, GLSL shaders only ever have one
+    pub const fn stages(self) -> &'static [ShaderStage] {{
+        match self {{
+{stages}        }}
+    }}
+
+    // The function that runs for `stage`, the first one if there are several
+    pub const fn entry_point(self, stage: ShaderStage) -> Option<&'static str> {{
+        match (self, stage) {{
+{entry_points}            _ => None,
+        }}
+    }}
+
+    // What `entry_point` is called inside of `source`, which is what wgpu needs
+    // Always `GLSL_ENTRY_POINT` for GLSL, WGSL keeps the names of its functions
+    pub const fn module_entry_point(self, stage: ShaderStage) -> Option<&'static str> {{
+        match (self, stage) {{
+{module_entry_points}            _ => None,
+        }}
+    }}
+
+    // Every entry point in the shader, `main` for GLSL unless the manifest gives others
+    pub const fn entry_points(self) -> &'static [(ShaderStage, &'static str)] {{
+        match self {{
+{all_entry_points}        }}
+    }}
+
+    pub const fn language(self) -> ShaderLanguage {{
+        match self {{
+{languages}        }}
+    }}
+
+    // SPIR-V for GLSL shaders, the validated source for WGSL ones
+    pub fn source(self) -> wgpu::ShaderSource<'static> {{
+        match self {{
+{sources}        }}
+    }}
+
+    // Files that were `#include`d when it was built, relative to the crate root unless they're outside of it
+    pub const fn includes(self) -> &'static [&'static str] {{
+        match self {{
+{includes}        }}
+    }}
+}}
+
+// Every variant from `src/shaders/manifest.toml`
+pub const VARIANTS: &[ShaderVariant] = &[
+{permutations}];
+
+// The separate modules of GLSL shaders with more than one entry point in `src/shaders/manifest.toml`
+pub const ENTRY_POINTS: &[ShaderEntryPoint] = &[
+{entry_point_modules}];
+",
+        variants = variants
+            .iter()
+            .map(|variant| format!("    {},\n", variant))
+            .collect::<String>(),
+        all = all,
+        names = names,
+        stages = stages,
+        entry_points = entry_points,
+        module_entry_points = module_entry_points,
+        all_entry_points = all_entry_points,
+        entry_point_modules = entry_point_modules,
+        languages = languages,
+        sources = sources,
+        includes = includes,
+        permutations = permutations,
+    ))
+}
@@ -0,0 +1,21 @@
+// An example playground shader, run it with `cargo run -- playground/plasma.frag`
+// Written the same way as a shader on https://www.shadertoy.com, `src/playground.rs` adds the rest
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+  // Pixel coordinates from 0 to 1, with (0, 0) in the bottom left
+  vec2 uv = fragCoord / iResolution.xy;
+
+  // A few overlapping waves that move with time
+  float v = sin(uv.x * 10.0 + iTime);
+  v += sin((uv.y * 10.0 + iTime) * 0.5);
+  v += sin((uv.x * 10.0 + uv.y * 10.0 + iTime) * 0.5);
+  vec3 color = 0.5 + 0.5 * cos(v + vec3(0.0, 2.0, 4.0));
+
+  // Draw a ring around where the mouse was clicked, while the button is held
+  if (iMouse.z > 0.0) {
+    float d = distance(fragCoord, iMouse.zw);
+    color += vec3(1.0 - smoothstep(0.0, 3.0, abs(d - 40.0)));
+  }
+
+  fragColor = vec4(color, 1.0);
+}
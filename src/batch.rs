@@ -0,0 +1,32 @@
+// Stuff that changes from frame to frame based off of input, read by `State::render`
+pub struct Batch {
+    // Index of the pipeline in `State::pipelines` that we're drawing with, cycled through with space
+    pub active_pipeline: usize,
+    // Cursor position
+    pub cursor_position: (f64, f64),
+    // Whether the left mouse button is being held down
+    pub mouse_pressed: bool,
+    // Where the cursor was when the left mouse button was last pressed
+    pub click_position: (f64, f64),
+    // Where the cursor was the last time it moved while the left mouse button was held
+    pub drag_position: (f64, f64),
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Batch {
+            active_pipeline: 0,
+            cursor_position: (0.0, 0.0),
+            mouse_pressed: false,
+            click_position: (0.0, 0.0),
+            drag_position: (0.0, 0.0),
+        }
+    }
+}
+
+// Clippy wants a `Default` whenever there's a `new()` that takes no arguments
+impl Default for Batch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,437 @@
+// Looking at the scene in 3D, instead of drawing everything straight into clip space
+//
+// Every frame `State` uploads the camera's view-projection matrix as `u_view_proj` (see `src/uniforms.rs`),
+// so a vertex shader puts its vertices into the scene with
+//
+// gl_Position = u_view_proj * vec4(a_position, 1.0);
+//
+// Matrices are `[[f32; 4]; 4]`s of columns, the same layout as GLSL's `mat4`
+// The world is right handed with y up, and wgpu's clip space has z from 0 at the near plane to 1 at the far plane
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+use winit::event::{
+    DeviceEvent, ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+pub type Matrix4 = [[f32; 4]; 4];
+
+pub const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// How the 3D scene gets flattened onto the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    // Things further away look smaller, `fovy` is how far the camera sees from the bottom to the top of the screen, in radians
+    Perspective { fovy: f32, near: f32, far: f32 },
+    // Things stay the same size however far away they are, `height` is how many units fit from the bottom to the top of the screen
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+pub struct Camera {
+    // Where the camera is
+    pub eye: [f32; 3],
+    // The point that it's looking at, which ends up in the middle of the screen
+    pub target: [f32; 3],
+    // Which way is up, so that the camera knows how to hold itself
+    pub up: [f32; 3],
+    // Width of the frame divided by its height, kept up to date by `State::resize`
+    pub aspect: f32,
+    pub projection: Projection,
+}
+
+impl Camera {
+    // Looking at the origin from 2 units in front of it, which fits `VERTICES` on the screen
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut camera = Self {
+            eye: [0.0, 0.0, 2.0],
+            target: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            aspect: 1.0,
+            projection: Projection::Perspective {
+                fovy: std::f32::consts::FRAC_PI_4,
+                near: 0.1,
+                far: 100.0,
+            },
+        };
+        camera.resize(width, height);
+        camera
+    }
+
+    // Minimized windows are 0 pixels tall, the old aspect ratio is as good as any until the window comes back
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    // Moves the world so that the camera is at the origin, looking down -z
+    pub fn view_matrix(&self) -> Matrix4 {
+        let forward = normalize(sub(self.target, self.eye));
+        let right = normalize(cross(forward, self.up));
+        let up = cross(right, forward);
+        [
+            [right[0], up[0], -forward[0], 0.0],
+            [right[1], up[1], -forward[1], 0.0],
+            [right[2], up[2], -forward[2], 0.0],
+            [
+                -dot(right, self.eye),
+                -dot(up, self.eye),
+                dot(forward, self.eye),
+                1.0,
+            ],
+        ]
+    }
+
+    // Takes what the camera sees to clip space, everything between the near and far plane ends up with z from 0 to 1
+    pub fn projection_matrix(&self) -> Matrix4 {
+        match self.projection {
+            Projection::Perspective { fovy, near, far } => {
+                let focal_length = 1.0 / (fovy / 2.0).tan();
+                [
+                    [focal_length / self.aspect, 0.0, 0.0, 0.0],
+                    [0.0, focal_length, 0.0, 0.0],
+                    [0.0, 0.0, far / (near - far), -1.0],
+                    [0.0, 0.0, near * far / (near - far), 0.0],
+                ]
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                [
+                    [1.0 / half_width, 0.0, 0.0, 0.0],
+                    [0.0, 1.0 / half_height, 0.0, 0.0],
+                    [0.0, 0.0, 1.0 / (near - far), 0.0],
+                    [0.0, 0.0, near / (near - far), 1.0],
+                ]
+            }
+        }
+    }
+
+    // What gets uploaded as `u_view_proj`
+    pub fn view_projection(&self) -> Matrix4 {
+        multiply(&self.projection_matrix(), &self.view_matrix())
+    }
+
+    // Switches between perspective and orthographic, keeping whatever is at the target about the same size on the screen
+    pub fn toggle_projection(&mut self) {
+        let distance = length(sub(self.eye, self.target));
+        self.projection = match self.projection {
+            Projection::Perspective { fovy, near, far } => Projection::Orthographic {
+                height: 2.0 * distance * (fovy / 2.0).tan(),
+                near,
+                far,
+            },
+            Projection::Orthographic { height, near, far } => Projection::Perspective {
+                fovy: 2.0 * (height / 2.0 / distance).atan(),
+                near,
+                far,
+            },
+        };
+    }
+}
+
+// `a * b`, so `b` gets applied to a vector first
+pub fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut result = [[0.0; 4]; 4];
+    for (column, b_column) in result.iter_mut().zip(b) {
+        for (row, value) in column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b_column[k]).sum();
+        }
+    }
+    result
+}
+
+// `matrix * vector`
+pub fn transform(matrix: &Matrix4, vector: [f32; 4]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = (0..4).map(|k| matrix[k][row] * vector[k]).sum();
+    }
+    result
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    scale(a, 1.0 / length(a))
+}
+
+// Looking straight up or down would make `up` and the view direction the same line, so pitch stops just short of that
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+// The unit vector from the target to the eye, for a camera that has turned `yaw` around the y axis and tilted up by `pitch`
+// Both 0 is looking down -z, like the default camera
+fn direction(yaw: f32, pitch: f32) -> [f32; 3] {
+    [
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    ]
+}
+
+// The other way around from `direction`, returns `(yaw, pitch)`
+fn angles(direction: [f32; 3]) -> (f32, f32) {
+    let direction = normalize(direction);
+    (
+        direction[0].atan2(direction[2]),
+        direction[1].clamp(-1.0, 1.0).asin(),
+    )
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    // Hold the right mouse button and drag to turn around the target, scroll to zoom
+    Orbit,
+    // WASD to move, Q and E to go down and up, the mouse to look around (the cursor gets grabbed)
+    Fly,
+}
+
+// Moves a `Camera` around based on the window events that `main` passes to `State::input`
+// Input is only collected as it comes in, `update_camera` applies it once a frame
+// Everything else is worked out from the camera itself, so changes made through `State::camera_mut` stick
+pub enum CameraController {
+    Orbit(OrbitController),
+    Fly(FlyController),
+}
+
+impl CameraController {
+    pub fn new(mode: CameraMode) -> Self {
+        match mode {
+            CameraMode::Orbit => CameraController::Orbit(OrbitController::new()),
+            CameraMode::Fly => CameraController::Fly(FlyController::new()),
+        }
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        match self {
+            CameraController::Orbit(_) => CameraMode::Orbit,
+            CameraController::Fly(_) => CameraMode::Fly,
+        }
+    }
+
+    // Returns whether the controller used the event, in which case `main` doesn't need to look at it
+    pub fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match self {
+            CameraController::Orbit(orbit) => orbit.window_event(event),
+            CameraController::Fly(fly) => fly.window_event(event),
+        }
+    }
+
+    // Raw mouse movement, which keeps coming while the cursor is grabbed (unlike `WindowEvent::CursorMoved`)
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        if let CameraController::Fly(fly) = self {
+            fly.device_event(event);
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        match self {
+            CameraController::Orbit(orbit) => orbit.update_camera(camera),
+            CameraController::Fly(fly) => fly.update_camera(camera, dt),
+        }
+    }
+}
+
+pub struct OrbitController {
+    // Radians per pixel that the cursor is dragged
+    pub sensitivity: f32,
+    rotating: bool,
+    last_cursor: Option<(f64, f64)>,
+    // How far the cursor was dragged since the last update, in pixels
+    drag: (f32, f32),
+    // How many lines were scrolled since the last update, positive is towards the target
+    scroll: f32,
+}
+
+impl OrbitController {
+    pub fn new() -> Self {
+        Self {
+            sensitivity: 0.01,
+            rotating: false,
+            last_cursor: None,
+            drag: (0.0, 0.0),
+            scroll: 0.0,
+        }
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.rotating = *state == ElementState::Pressed;
+                true
+            }
+            // The batch needs the cursor position too, so this one gets passed on
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some((x, y))) = (self.rotating, self.last_cursor) {
+                    self.drag.0 += (position.x - x) as f32;
+                    self.drag.1 += (position.y - y) as f32;
+                }
+                self.last_cursor = Some((position.x, position.y));
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    // Touchpads scroll in pixels, roughly 20 of them to a line
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera) {
+        if self.drag == (0.0, 0.0) && self.scroll == 0.0 {
+            return;
+        }
+        let offset = sub(camera.eye, camera.target);
+        let (yaw, pitch) = angles(offset);
+        // Dragging right swings the camera left, so the scene looks like it turns along with the cursor
+        let yaw = yaw - self.drag.0 * self.sensitivity;
+        let pitch = (pitch + self.drag.1 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        // Each line scrolled gets 10% closer, so zooming feels the same whether we're close or far away
+        let zoom = 0.9f32.powf(self.scroll);
+        let distance = (length(offset) * zoom).max(0.01);
+        camera.eye = add(camera.target, scale(direction(yaw, pitch), distance));
+        // Orthographic cameras don't get bigger when they get closer, so zoom those by shrinking what fits on the screen
+        if let Projection::Orthographic { height, .. } = &mut camera.projection {
+            *height *= zoom;
+        }
+        self.drag = (0.0, 0.0);
+        self.scroll = 0.0;
+    }
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct FlyController {
+    // Units per second
+    pub speed: f32,
+    // Radians per unit of raw mouse movement, which is roughly a pixel
+    pub sensitivity: f32,
+    // How far the mouse moved since the last update
+    look: (f32, f32),
+    // Which of W, A, S, D, Q and E are held down
+    forward: bool,
+    left: bool,
+    backward: bool,
+    right: bool,
+    down: bool,
+    up: bool,
+}
+
+impl FlyController {
+    pub fn new() -> Self {
+        Self {
+            speed: 2.0,
+            sensitivity: 0.003,
+            look: (0.0, 0.0),
+            forward: false,
+            left: false,
+            backward: false,
+            right: false,
+            down: false,
+            up: false,
+        }
+    }
+
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        let input = match event {
+            WindowEvent::KeyboardInput { input, .. } => input,
+            _ => return false,
+        };
+        let pressed = input.state == ElementState::Pressed;
+        let held = match input.virtual_keycode {
+            Some(VirtualKeyCode::W) => &mut self.forward,
+            Some(VirtualKeyCode::A) => &mut self.left,
+            Some(VirtualKeyCode::S) => &mut self.backward,
+            Some(VirtualKeyCode::D) => &mut self.right,
+            Some(VirtualKeyCode::Q) => &mut self.down,
+            Some(VirtualKeyCode::E) => &mut self.up,
+            _ => return false,
+        };
+        *held = pressed;
+        true
+    }
+
+    fn device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.look.0 += delta.0 as f32;
+            self.look.1 += delta.1 as f32;
+        }
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let moving =
+            self.forward || self.left || self.backward || self.right || self.down || self.up;
+        if self.look == (0.0, 0.0) && !moving {
+            return;
+        }
+        let (yaw, pitch) = angles(sub(camera.eye, camera.target));
+        // Moving the mouse right turns right and moving it down looks down
+        let yaw = yaw - self.look.0 * self.sensitivity;
+        let pitch = (pitch + self.look.1 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        self.look = (0.0, 0.0);
+
+        let forward = scale(direction(yaw, pitch), -1.0);
+        let right = normalize(cross(forward, camera.up));
+        // `true as i32 as f32` is 1, so opposite keys cancel out
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let step = self.speed * dt.as_secs_f32();
+        let movement = add(
+            add(
+                scale(forward, axis(self.forward, self.backward)),
+                scale(right, axis(self.right, self.left)),
+            ),
+            scale(camera.up, axis(self.up, self.down)),
+        );
+        camera.eye = add(camera.eye, scale(movement, step));
+        // The target stays one unit in front, only the direction matters while flying
+        camera.target = add(camera.eye, forward);
+    }
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
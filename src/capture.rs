@@ -0,0 +1,216 @@
+// Reading rendered pixels back from the GPU to the CPU, and saving them as images
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// When copying a texture into a buffer, every row in the buffer has to start at a multiple of
+// `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` (256) bytes, so rows usually end up with some padding at the end
+pub struct BufferDimensions {
+    pub width: u32,
+    pub height: u32,
+    // Bytes that one row of pixels actually takes up
+    pub unpadded_bytes_per_row: u32,
+    // Bytes that one row takes up in the buffer, including the padding
+    pub padded_bytes_per_row: u32,
+}
+
+impl BufferDimensions {
+    pub fn new(width: u32, height: u32, bytes_per_pixel: u32) -> Self {
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        // Round up to the next multiple of `align`
+        let padding = (align - unpadded_bytes_per_row % align) % align;
+        Self {
+            width,
+            height,
+            unpadded_bytes_per_row,
+            padded_bytes_per_row: unpadded_bytes_per_row + padding,
+        }
+    }
+
+    // Total size of the buffer that the texture gets copied into
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+        (self.padded_bytes_per_row * self.height) as wgpu::BufferAddress
+    }
+}
+
+// Creates a buffer that can be copied into by the GPU and then mapped (read) by the CPU
+pub fn create_readback_buffer(
+    device: &wgpu::Device,
+    dimensions: &BufferDimensions,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: dimensions.buffer_size(),
+        // `COPY_DST` so that we can copy the texture into it, `MAP_READ` so that we can read it on the CPU
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
+
+// Records a command that copies the whole of `texture` into `buffer`
+// The texture has to be created with `TextureUsage::COPY_SRC`
+pub fn copy_texture_to_buffer(
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    buffer: &wgpu::Buffer,
+    dimensions: &BufferDimensions,
+) {
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: dimensions.padded_bytes_per_row,
+                rows_per_image: dimensions.height,
+            },
+        },
+        wgpu::Extent3d {
+            width: dimensions.width,
+            height: dimensions.height,
+            depth: 1,
+        },
+    );
+}
+
+// Waits for the copy into `buffer` to finish, then returns its pixels with the row padding removed
+// This blocks the current thread until the GPU is done with everything that has been submitted so far
+pub fn read_buffer(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    dimensions: &BufferDimensions,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    // The future only resolves once the device has been polled
+    let mapping = buffer.slice(..).map_async(wgpu::MapMode::Read);
+    device.poll(wgpu::Maintain::Wait);
+    futures::executor::block_on(mapping)?;
+
+    Ok(read_mapped_buffer(buffer, dimensions))
+}
+
+// Returns the pixels of a buffer that has already been mapped, with the row padding removed, and unmaps it
+pub fn read_mapped_buffer(buffer: &wgpu::Buffer, dimensions: &BufferDimensions) -> Vec<u8> {
+    let pixels = {
+        let padded = buffer.slice(..).get_mapped_range();
+        // Copy each row over, skipping the padding at the end of it
+        padded
+            .chunks(dimensions.padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..dimensions.unpadded_bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<u8>>()
+    };
+    // The mapped range has to be dropped before we're allowed to unmap
+    buffer.unmap();
+
+    pixels
+}
+
+// How many bytes one pixel of `format` takes up, only the color formats that we render to are supported
+pub fn bytes_per_pixel(format: wgpu::TextureFormat) -> Option<u32> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Rgba8UnormSrgb
+        | wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb => Some(4),
+        _ => None,
+    }
+}
+
+// Everything that can go wrong between asking for a frame and having it on disk
+#[derive(Debug)]
+pub enum CaptureError {
+    // Mapping the readback buffer failed
+    Map(wgpu::BufferAsyncError),
+    // We don't know how to turn pixels of this format into RGBA
+    UnsupportedFormat(wgpu::TextureFormat),
+    // Encoding or writing the image failed
+    Image(image::ImageError),
+    // Writing a file (other than an image) failed
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::Map(e) => write!(f, "failed to map readback buffer: {:?}", e),
+            CaptureError::UnsupportedFormat(format) => {
+                write!(f, "can't convert {:?} pixels to RGBA", format)
+            }
+            CaptureError::Image(e) => write!(f, "failed to write image: {}", e),
+            CaptureError::Io(e) => write!(f, "failed to write file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<wgpu::BufferAsyncError> for CaptureError {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        CaptureError::Map(e)
+    }
+}
+
+impl From<image::ImageError> for CaptureError {
+    fn from(e: image::ImageError) -> Self {
+        CaptureError::Image(e)
+    }
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+// Converts tightly packed pixels of `format` into RGBA, in place
+// The swap chain usually prefers BGRA, so most of the time this just swaps the red and blue channels
+// sRGB and linear formats store their bytes the same way that a PNG does, so there's nothing else to convert
+pub fn convert_to_rgba8(
+    format: wgpu::TextureFormat,
+    pixels: &mut [u8],
+) -> Result<(), CaptureError> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(()),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+            Ok(())
+        }
+        _ => Err(CaptureError::UnsupportedFormat(format)),
+    }
+}
+
+// Saves pixels that were read back from a texture of `format` as an RGBA PNG
+pub fn save_png(
+    path: &Path,
+    dimensions: &BufferDimensions,
+    format: wgpu::TextureFormat,
+    mut pixels: Vec<u8>,
+) -> Result<(), CaptureError> {
+    convert_to_rgba8(format, &mut pixels)?;
+    image::save_buffer(
+        path,
+        &pixels,
+        dimensions.width,
+        dimensions.height,
+        image::ColorType::Rgba8,
+    )?;
+    Ok(())
+}
+
+// Something like `screenshot-1618033988749.png`, milliseconds since the unix epoch so that names never clash and sort in order
+// An empty `extension` gives a name without one, e.g. for a directory
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    let mut path = PathBuf::from(format!("{}-{}", prefix, millis));
+    path.set_extension(extension);
+    path
+}
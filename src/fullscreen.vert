@@ -0,0 +1,12 @@
+// `fullscreen.vert`
+// One triangle that covers the whole screen, made up from `gl_VertexIndex` just like `shader2.vert`
+// Used by the playground, where the fragment shader does all of the work
+
+#version 460
+
+void main() {
+  // The vertices end up at (-1, -1), (3, -1) and (-1, 3), counterclockwise
+  // The parts of the triangle that hang off the screen just get clipped away
+  vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+  gl_Position = vec4(position, 0.0, 1.0);
+}
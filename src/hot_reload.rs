@@ -0,0 +1,171 @@
+// Recompiling shaders while the program is running, so that tweaking a shader doesn't need a rebuild and restart
+//
+// `ShaderWatcher` keeps an eye on the modification times of the shader sources and the files that they `#include`,
+// and compiles the ones that changed
+// `State::update` then swaps the new modules in and rebuilds the pipelines that use them
+use crate::shader::{CompiledShader, ShaderError};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// Checking every file every frame would be a lot of wasted system calls
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Turns a source file into something that can be made into a module, e.g. `shader::compile_file` or `playground::compile`
+// Boxed so that it can hold on to things like the defines of a variant
+pub type CompileFn = Box<dyn Fn(&Path) -> Result<CompiledShader, ShaderError>>;
+
+struct WatchedFile {
+    // Name of the shader in the `ShaderLibrary`
+    name: String,
+    path: PathBuf,
+    compile: CompileFn,
+    // `None` if the file couldn't be read last time, so it gets compiled as soon as it shows up
+    modified: Option<SystemTime>,
+    // The `#include`d files, editing any of them recompiles the shader as well
+    includes: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+fn with_modified(includes: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    includes
+        .iter()
+        .map(|include| (include.clone(), modified(include)))
+        .collect()
+}
+
+// A shader whose source changed, and what came out of compiling it
+pub struct Reload {
+    pub name: String,
+    pub path: PathBuf,
+    pub result: Result<CompiledShader, ShaderError>,
+}
+
+pub struct ShaderWatcher {
+    files: Vec<WatchedFile>,
+    last_poll: Instant,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Starts watching the source of the shader called `name` and the files in `includes`,
+    // replacing whatever files that shader was watched at before
+    // The files as they are right now are assumed to be what's already loaded
+    // Which files are included gets updated every time the shader compiles, in case an `#include` was added or removed
+    pub fn watch(
+        &mut self,
+        name: &str,
+        path: PathBuf,
+        includes: &[PathBuf],
+        compile: impl Fn(&Path) -> Result<CompiledShader, ShaderError> + 'static,
+    ) {
+        self.files.retain(|file| file.name != name);
+        self.files.push(WatchedFile {
+            name: name.to_string(),
+            modified: modified(&path),
+            path,
+            compile: Box::new(compile),
+            includes: with_modified(includes),
+        });
+    }
+
+    // Compiles every shader whose source changed since the last poll
+    // Does nothing if the last poll was less than `POLL_INTERVAL` ago
+    pub fn poll(&mut self) -> Vec<Reload> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut reloads = Vec::new();
+        for file in &mut self.files {
+            let source_modified = modified(&file.path);
+            // Editors sometimes delete and recreate the file when saving, wait until it's back
+            let source_changed = source_modified.is_some() && source_modified != file.modified;
+            let include_changed = file.includes.iter().any(|(include, last)| {
+                let now = modified(include);
+                now.is_some() && now != *last
+            });
+            if !source_changed && !include_changed {
+                continue;
+            }
+            file.modified = source_modified;
+            let result = (file.compile)(&file.path);
+            // A failed compile might not have gotten to every `#include`, so the old ones stay watched
+            let includes = match &result {
+                Ok(compiled) => compiled.includes().to_vec(),
+                Err(_) => file
+                    .includes
+                    .iter()
+                    .map(|(include, _)| include.clone())
+                    .collect(),
+            };
+            file.includes = with_modified(&includes);
+            reloads.push(Reload {
+                name: file.name.clone(),
+                path: file.path.clone(),
+                result,
+            });
+        }
+        reloads
+    }
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// wgpu 0.7 doesn't have error scopes, and by default any validation error panics
+// This replaces the device's error handler so that errors can be caught around a few calls instead
+// Errors outside of `catch` still panic, just like they did before
+#[derive(Clone)]
+pub struct ErrorCatcher {
+    // `Some` while inside of `catch`, collecting every error that happens
+    caught: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl ErrorCatcher {
+    pub fn install(device: &wgpu::Device) -> Self {
+        let caught = Arc::new(Mutex::new(None::<Vec<String>>));
+        let handler_caught = caught.clone();
+        device.on_uncaptured_error(move |error| {
+            // Make sure the lock is released before panicking, so that it doesn't get poisoned
+            let uncaught = match &mut *handler_caught.lock().unwrap() {
+                Some(errors) => {
+                    errors.push(error.to_string());
+                    None
+                }
+                None => Some(error),
+            };
+            if let Some(error) = uncaught {
+                panic!("wgpu error: {}", error);
+            }
+        });
+        Self { caught }
+    }
+
+    // Runs `f`, returning the messages of every wgpu error that happened while it ran
+    pub fn catch<T>(&self, f: impl FnOnce() -> T) -> Result<T, Vec<String>> {
+        *self.caught.lock().unwrap() = Some(Vec::new());
+        let result = f();
+        let errors = self.caught.lock().unwrap().take().unwrap_or_default();
+        if errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Resolving `#include "..."` and `#include <...>` in GLSL, for both `build.rs` and the runtime compiler in `shader.rs`
+// `build.rs` pulls this file in with `#[path]`, so it can only use `std` and `shaderc`, nothing else from the crate
+//
+// - `#include "file.glsl"` looks next to the file that includes it first, then falls back to the include directories
+// - `#include <file.glsl>` only looks in the include directories
+use std::path::{Path, PathBuf};
+
+// Always searched, relative to the crate root
+pub const DEFAULT_INCLUDE_DIRS: &[&str] = &["src/shaders/include"];
+// Extra directories to search, separated the same way as `PATH`, relative to the crate root unless they're absolute
+pub const INCLUDE_PATH_VAR: &str = "SHADER_INCLUDE_PATH";
+// Built into the binary, so that `#include <globals.glsl>` still works once the program is copied away from `src/`
+// A file with the same name in the include directories comes first, so that editing it still gets picked up
+pub const BUILTIN_INCLUDES: &[(&str, &str)] =
+    &[("globals.glsl", include_str!("shaders/include/globals.glsl"))];
+// Stops two files that include each other from recursing forever
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+// Every directory that `#include` searches, in order
+pub fn include_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = DEFAULT_INCLUDE_DIRS
+        .iter()
+        .map(|dir| root.join(dir))
+        .collect::<Vec<_>>();
+    if let Some(extra) = std::env::var_os(INCLUDE_PATH_VAR) {
+        // `join` with an absolute path just gives back the absolute path
+        dirs.extend(std::env::split_paths(&extra).map(|dir| root.join(dir)));
+    }
+    dirs
+}
+
+// Finds the file that an `#include` refers to
+// `requesting` is the name of the file with the `#include` in it, which is a path for everything that we compile
+fn find(
+    requested: &str,
+    include_type: shaderc::IncludeType,
+    requesting: &str,
+    dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let candidates = match include_type {
+        shaderc::IncludeType::Relative => Path::new(requesting)
+            .parent()
+            .map(|dir| dir.join(requested))
+            .into_iter()
+            .collect::<Vec<_>>(),
+        shaderc::IncludeType::Standard => dirs.iter().map(|dir| dir.join(requested)).collect(),
+    };
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+// Makes `options` resolve includes from `dirs`
+// `on_include` gets called with every file that gets included, e.g. so that `build.rs` can tell cargo to watch it
+pub fn set_include_callback<'a>(
+    options: &mut shaderc::CompileOptions<'a>,
+    dirs: Vec<PathBuf>,
+    on_include: impl Fn(&Path) + 'a,
+) {
+    // If a `Relative` include fails, shaderc tries again as `Standard`, so quoted includes fall back to `dirs` for free
+    options.set_include_callback(move |requested, include_type, requesting, depth| {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(format!(
+                "#include nested more than {} deep, do some files include each other?",
+                MAX_INCLUDE_DEPTH
+            ));
+        }
+        let path = match find(requested, include_type, requesting, &dirs) {
+            Some(path) => path,
+            None => return builtin(requested, include_type, &dirs),
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        on_include(&path);
+        Ok(shaderc::ResolvedInclude {
+            resolved_name: path.to_string_lossy().into_owned(),
+            content,
+        })
+    });
+}
+
+// Only for `Standard` lookups, a `Relative` one that fails gets tried again as `Standard` anyway
+// Nothing gets passed to `on_include`, since there's no file to watch
+fn builtin(
+    requested: &str,
+    include_type: shaderc::IncludeType,
+    dirs: &[PathBuf],
+) -> Result<shaderc::ResolvedInclude, String> {
+    BUILTIN_INCLUDES
+        .iter()
+        .find(|(name, _)| *name == requested && include_type == shaderc::IncludeType::Standard)
+        .map(|(name, content)| shaderc::ResolvedInclude {
+            resolved_name: format!("<built-in>/{}", name),
+            content: content.to_string(),
+        })
+        .ok_or_else(|| format!("couldn't find `{}` in {:?}", requested, dirs))
+}
@@ -0,0 +1,202 @@
+// Drawing many copies of the same mesh in one draw call, each with its own transform and color
+//
+// The mesh's vertices are bound to slot 0 like usual, and a buffer of `Instance`s to slot 1
+// The vertex shader gets a new `Instance` for every copy instead of for every vertex, in GLSL
+//
+// layout(location=2) in vec4 a_model_0; // The model matrix takes up locations 2 to 5, one per column
+// layout(location=3) in vec4 a_model_1;
+// layout(location=4) in vec4 a_model_2;
+// layout(location=5) in vec4 a_model_3;
+// layout(location=6) in vec4 a_instance_color;
+//
+// and places each copy with `gl_Position = u_view_proj * model * vec4(a_position, 1.0);`
+// The columns can't be a single `in mat4`, wgpu only matches vertex attributes against scalars and vectors
+use crate::{Mesh, VertexLayout};
+use std::collections::HashMap;
+use std::ops::Range;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+#[step_mode(instance)]
+pub struct Instance {
+    // Takes the mesh's positions to where this copy is in the world
+    // Written out instead of `Matrix4`, the derive can only tell that it's a matrix from the `[[...]]`
+    #[location(2)]
+    pub model: [[f32; 4]; 4],
+    // Multiplied with the mesh's own vertex colors, so white leaves them as they are
+    #[location(6)]
+    pub color: [f32; 4],
+}
+
+impl Instance {
+    // A copy moved to `position` and made `scale` times bigger, without any rotation
+    pub fn at(position: [f32; 3], scale: f32, color: [f32; 4]) -> Self {
+        let [x, y, z] = position;
+        Self {
+            model: [
+                [scale, 0.0, 0.0, 0.0],
+                [0.0, scale, 0.0, 0.0],
+                [0.0, 0.0, scale, 0.0],
+                [x, y, z, 1.0],
+            ],
+            color,
+        }
+    }
+}
+
+// Refers to one instance of an `InstancedMesh`, stays the same while other instances are added and removed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId(u64);
+
+// The GPU buffer starts out with room for this many instances, and doubles whenever it runs out
+const MIN_CAPACITY: usize = 64;
+
+// A mesh along with every copy of it that gets drawn
+// Changes are made on the CPU side and only the part of the buffer that changed is uploaded, once per frame in `upload`
+pub struct InstancedMesh {
+    label: String,
+    pub mesh: Mesh,
+    // Kept packed, so that the whole thing can be drawn as `0..instances.len()`
+    instances: Vec<Instance>,
+    // `ids[i]` is the id of `instances[i]`
+    ids: Vec<InstanceId>,
+    // The other way around, where each id's instance is in `instances`
+    indices: HashMap<InstanceId, usize>,
+    next_id: u64,
+    // `None` until there's something to draw
+    buffer: Option<wgpu::Buffer>,
+    // How many instances fit in `buffer`
+    capacity: usize,
+    // Which instances changed since the last upload
+    dirty: Option<Range<usize>>,
+    // How many instances there were at the last upload, `draw` only draws these
+    uploaded: usize,
+}
+
+impl InstancedMesh {
+    pub fn new(label: &str, mesh: Mesh) -> Self {
+        Self {
+            label: label.to_string(),
+            mesh,
+            instances: Vec::new(),
+            ids: Vec::new(),
+            indices: HashMap::new(),
+            next_id: 0,
+            buffer: None,
+            capacity: 0,
+            dirty: None,
+            uploaded: 0,
+        }
+    }
+
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        let id = InstanceId(self.next_id);
+        self.next_id += 1;
+        let index = self.instances.len();
+        self.instances.push(instance);
+        self.ids.push(id);
+        self.indices.insert(id, index);
+        self.mark_dirty(index);
+        id
+    }
+
+    // Returns `false` if the instance was already removed
+    pub fn update(&mut self, id: InstanceId, instance: Instance) -> bool {
+        match self.indices.get(&id) {
+            Some(&index) => {
+                self.instances[index] = instance;
+                self.mark_dirty(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The last instance gets moved into the gap, so only that one has to be uploaded again
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let index = self.indices.remove(&id)?;
+        let instance = self.instances.swap_remove(index);
+        self.ids.swap_remove(index);
+        if let Some(&moved) = self.ids.get(index) {
+            self.indices.insert(moved, index);
+            self.mark_dirty(index);
+        }
+        Some(instance)
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        self.indices.get(&id).map(|&index| &self.instances[index])
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.ids.clear();
+        self.indices.clear();
+        self.dirty = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    // How many instances `draw` draws, which can be behind `len` until the next `upload`
+    pub fn uploaded_len(&self) -> usize {
+        self.uploaded
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(index)..dirty.end.max(index + 1),
+            None => index..index + 1,
+        });
+    }
+
+    // Gets the buffer up to date with the instances, `State::render` calls this before drawing
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        // Instances that were removed from the end don't need to be uploaded, they just aren't drawn anymore
+        let dirty = self
+            .dirty
+            .take()
+            .map(|dirty| dirty.start..dirty.end.min(self.instances.len()));
+        if self.instances.len() > self.capacity {
+            // A bigger buffer starts out empty, so everything goes into it
+            self.capacity = self.instances.len().next_power_of_two().max(MIN_CAPACITY);
+            let label = format!("{} Instance Buffer", self.label);
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&label),
+                size: (self.capacity * std::mem::size_of::<Instance>()) as wgpu::BufferAddress,
+                // `COPY_DST` so that `queue.write_buffer` can change it
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            self.write(queue, 0..self.instances.len());
+        } else if let Some(dirty) = dirty.filter(|dirty| !dirty.is_empty()) {
+            self.write(queue, dirty);
+        }
+        self.uploaded = self.instances.len();
+    }
+
+    fn write(&self, queue: &wgpu::Queue, range: Range<usize>) {
+        if let Some(buffer) = &self.buffer {
+            let offset = (range.start * std::mem::size_of::<Instance>()) as wgpu::BufferAddress;
+            queue.write_buffer(buffer, offset, bytemuck::cast_slice(&self.instances[range]));
+        }
+    }
+
+    // Binds the instances to slot 1 and draws every one of them with a single draw call
+    // Draws the instances as they were at the last `upload`, anything changed after that shows up once it's called again
+    // Otherwise added instances would be drawn from whatever was left in the buffer, and removed ones would stay
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let count = self.uploaded;
+        let buffer = match &self.buffer {
+            Some(buffer) if count > 0 => buffer,
+            _ => return,
+        };
+        render_pass.set_vertex_buffer(1, buffer.slice(..));
+        self.mesh.draw(render_pass, 0..count as u32);
+    }
+}
@@ -0,0 +1,34 @@
+// The renderer lives in a library so that both the windowed binary (`main.rs`) and the tests can use it
+
+// `#[derive(VertexLayout)]` refers to things as `::bottle_water::...`, this makes that work inside of this crate too
+extern crate self as bottle_water;
+
+pub mod batch;
+pub mod camera;
+pub mod capture;
+pub mod hot_reload;
+pub mod includes;
+pub mod instance;
+pub mod mesh;
+pub mod pipeline;
+pub mod playground;
+pub mod record;
+pub mod reflect;
+pub mod shader;
+pub mod state;
+pub mod texture;
+pub mod uniforms;
+pub mod vertex;
+
+// Re-export the things that `main` needs so it doesn't have to know about the modules
+pub use batch::Batch;
+pub use camera::{Camera, CameraMode, Projection};
+pub use instance::{Instance, InstanceId, InstancedMesh};
+pub use mesh::Mesh;
+pub use shader::{BuiltinShader, ShaderLanguage, ShaderStage};
+pub use state::State;
+pub use texture::Texture;
+pub use vertex::{
+    TexturedVertex, Vertex, VertexLayout, PENTAGON_INDICES, PENTAGON_VERTICES, QUAD_INDICES,
+    QUAD_VERTICES, VERTICES,
+};
@@ -1,10 +1,15 @@
+// All of the rendering code lives in `lib.rs`
+use bottle_water::{
+    capture, record::RecordingOutput, Batch, CameraMode, Instance, Mesh, State, VERTICES,
+};
+use std::path::PathBuf;
+use std::time::Instant;
 // Winit allows us to make windows
-use wgpu::util::DeviceExt;
 use winit::{
     // Import all event types
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::WindowBuilder,
 };
 
 fn main() {
@@ -23,12 +28,66 @@ fn main() {
     let mut state = block_on(State::new(&window));
     let mut batch = Batch::new();
 
+    // `cargo run -- --hot-reload` recompiles shaders whenever their source changes (see `src/hot_reload.rs`)
+    // `cargo run -- path/to/shader.frag` starts in the playground with that shader (see `src/playground.rs`)
+    // `cargo run -- --texture path/to/image.png` draws that image on the textured quad instead of the checkerboard
+    // `cargo run -- --msaa 4` draws with 4 samples per pixel (1, 2, 4 or 8, if the adapter supports it)
+    let mut playground_path = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--hot-reload" {
+            state.enable_hot_reload();
+        } else if arg == "--texture" {
+            let path = match args.next() {
+                Some(path) => PathBuf::from(path),
+                None => {
+                    eprintln!("--texture needs the path of an image after it");
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = state.load_texture(&path) {
+                eprintln!("Failed to load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        } else if arg == "--msaa" {
+            let count = match args.next().and_then(|count| count.to_str()?.parse().ok()) {
+                Some(count) => count,
+                None => {
+                    eprintln!("--msaa needs the number of samples per pixel after it");
+                    std::process::exit(1);
+                }
+            };
+            if let Err(e) = state.set_sample_count(count) {
+                eprintln!("Failed to turn on MSAA: {}", e);
+                std::process::exit(1);
+            }
+        } else {
+            playground_path = Some(PathBuf::from(arg));
+        }
+    }
+    if let Some(path) = playground_path {
+        match state.load_playground(&path) {
+            Ok(index) => batch.active_pipeline = index,
+            Err(e) => {
+                eprintln!("Failed to load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    add_marker_grid(&mut state);
+    // Used to work out how much time passed between frames
+    let mut last_frame = Instant::now();
+
     // TODO Don't know what the fk clojures are RIP
     event_loop.run(move |event, _, control_flow| {
         // Listen to window close event to exit if window close is pressed?
         match event {
             Event::RedrawRequested(_) => {
-                state.update();
+                let now = Instant::now();
+                // While recording, time moves forward by exactly one frame no matter how long the frame actually took
+                let dt = state.fixed_timestep().unwrap_or(now - last_frame);
+                last_frame = now;
+                state.update(dt);
                 match state.render(&batch) {
                     Ok(_) => {}
                     // Recreate swap chain if lost
@@ -40,6 +99,14 @@ fn main() {
                     Err(e) => eprintln!("{:?}", e),
                 }
             }
+            // Make sure that the last few recorded frames make it to disk before we exit
+            Event::LoopDestroyed => {
+                if let Err(e) = state.stop_recording() {
+                    eprintln!("Recording failed: {}", e);
+                }
+            }
+            // Raw mouse movement, which the fly camera keeps getting even while the cursor is grabbed
+            Event::DeviceEvent { ref event, .. } => state.device_input(event),
             Event::MainEventsCleared => {
                 // We must keep requesting redraws, else `RedrawRequested` event will only trigger once
                 window.request_redraw();
@@ -61,6 +128,21 @@ fn main() {
                             // Update batch
                             batch.cursor_position.0 = position.x;
                             batch.cursor_position.1 = position.y;
+                            if batch.mouse_pressed {
+                                batch.drag_position = batch.cursor_position;
+                            }
+                        }
+                        // Left mouse button presses, for the playground's `iMouse`
+                        WindowEvent::MouseInput {
+                            state: button_state,
+                            button: MouseButton::Left,
+                            ..
+                        } => {
+                            batch.mouse_pressed = *button_state == ElementState::Pressed;
+                            if batch.mouse_pressed {
+                                batch.click_position = batch.cursor_position;
+                                batch.drag_position = batch.cursor_position;
+                            }
                         }
                         WindowEvent::KeyboardInput { input, .. } => {
                             // Match the attributes of the keypress
@@ -80,10 +162,47 @@ fn main() {
                                         Some(VirtualKeyCode::Escape) => {
                                             *control_flow = ControlFlow::Exit
                                         }
-                                        // If spacebar is pressed update batch
+                                        // If spacebar is pressed switch to the next pipeline
                                         Some(VirtualKeyCode::Space) => {
-                                            batch.space_pressed = !batch.space_pressed
+                                            batch.active_pipeline =
+                                                state.pipelines().next_index(batch.active_pipeline)
+                                        }
+                                        // Switch between the orbit and the fly camera, the fly camera hides the cursor and keeps it in the window
+                                        Some(VirtualKeyCode::C) => {
+                                            let mode = match state.camera_mode() {
+                                                CameraMode::Orbit => CameraMode::Fly,
+                                                CameraMode::Fly => CameraMode::Orbit,
+                                            };
+                                            state.set_camera_mode(mode);
+                                            let fly = mode == CameraMode::Fly;
+                                            if let Err(e) = window.set_cursor_grab(fly) {
+                                                eprintln!("Couldn't grab the cursor: {}", e);
+                                            }
+                                            window.set_cursor_visible(!fly);
+                                        }
+                                        // Switch between a perspective and an orthographic camera
+                                        Some(VirtualKeyCode::P) => {
+                                            state.camera_mut().toggle_projection()
                                         }
+                                        // Save the next frame as a PNG in the current directory
+                                        Some(VirtualKeyCode::F12) => state.request_screenshot(
+                                            capture::timestamped_path("screenshot", "png"),
+                                        ),
+                                        // Start or stop recording every frame as numbered PNGs in a new directory
+                                        Some(VirtualKeyCode::F9) => toggle_recording(
+                                            &mut state,
+                                            RecordingOutput::ImageSequence(
+                                                capture::timestamped_path("recording", ""),
+                                            ),
+                                        ),
+                                        // Start or stop recording every frame to a Y4M video
+                                        Some(VirtualKeyCode::F10) => toggle_recording(
+                                            &mut state,
+                                            RecordingOutput::Y4m(capture::timestamped_path(
+                                                "recording",
+                                                "y4m",
+                                            )),
+                                        ),
                                         _ => (),
                                     }
                                 }
@@ -105,412 +224,42 @@ fn main() {
     });
 }
 
-struct Batch {
-    // 0 or 1
-    space_pressed: bool,
-    // Cursor position
-    cursor_position: (f64, f64),
-}
+// How many markers `add_marker_grid` puts along each side
+const MARKER_GRID_SIZE: u32 = 150;
 
-impl Batch {
-    fn new() -> Self {
-        Batch {
-            space_pressed: false,
-            cursor_position: (0.0, 0.0),
+// Gives the `instanced` pipeline something to draw, a grid of tens of thousands of tiny triangles in one draw call
+fn add_marker_grid(state: &mut State) {
+    let mesh = Mesh::new(&state.device, "Marker", VERTICES);
+    let index = state.add_instanced_mesh("Markers", mesh);
+    let markers = state.instanced_mesh_mut(index).unwrap();
+    let spacing = 2.0 / MARKER_GRID_SIZE as f32;
+    for y in 0..MARKER_GRID_SIZE {
+        for x in 0..MARKER_GRID_SIZE {
+            // From -1 to 1 on both axes, with a color that fades across the grid
+            let (u, v) = (
+                x as f32 / (MARKER_GRID_SIZE - 1) as f32,
+                y as f32 / (MARKER_GRID_SIZE - 1) as f32,
+            );
+            let position = [u * 2.0 - 1.0, v * 2.0 - 1.0, 0.0];
+            markers.add(Instance::at(position, spacing, [u, v, 1.0 - u, 1.0]));
         }
     }
 }
 
-// `bytemuck::Pod` means that out data is just "Plain Old Data"
-// `bytemuck::Zeroable` means that we can use `std::mem::zeroed()`
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
-}
-
-// Gotta write traits manually if some fields ddon't implement these
-// unsafe impl bytemuck::Pod for Vertex {}
-// unsafe impl bytemuck::Zeroable for Vertex {}
-
-impl Vertex {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        // Descirbes how a buffer is layed out in memory;
-        // How to map buffer to the shader
-        wgpu::VertexBufferLayout {
-            // Describes how wide the vertex is
-            // in this case we can literally pass in the size of vertex that we defined in `Vertex`
-            // `wgpu::BufferAddress` is internal type for buffer steps
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            // Tells pipeline how often it should move to the next vertex
-            step_mode: wgpu::InputStepMode::Vertex,
-            // Describe the individual parts of the vertex, mirrors `Vertex` fields
-            attributes: &[
-                // This describes vector position
-                wgpu::VertexAttribute {
-                    // Where the first attribute is
-                    offset: 0,
-                    // Where we should store this attribute
-                    // layout(location=0) would be position
-                    // layout(location=1) would be color
-                    shader_location: 0,
-                    // Shape of the attribute, Float3 == Vec3
-                    format: wgpu::VertexFormat::Float3,
-                },
-                // This describes vector color
-                wgpu::VertexAttribute {
-                    // Offset by the vector position data as described above
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    // Where we should store this attribute, `layout(location=1)`
-                    shader_location: 1,
-                    // Format is same as defined in `Vertex`
-                    format: wgpu::VertexFormat::Float3,
-                },
-            ],
-            // attributes: &wgpu::vertex_attr_array![0 => Float3, 1=> Float3],
-        }
-    }
-}
-
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [0.0, 0.5, 0.0],
-        color: [1.0, 0.0, 0.0],
-    },
-    Vertex {
-        position: [-0.5, -0.5, 0.0],
-        color: [0.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [0.5, -0.5, 0.0],
-        color: [0.0, 0.0, 1.0],
-    },
-];
-
-struct State {
-    // Platform specific "surface" in which rendered images can be put
-    surface: wgpu::Surface,
-    // Open connection to the GPU, responsible for creating rendering and compute processes in the form of commands, which are submitted to the queue
-    device: wgpu::Device,
-    // Executes the command buffer, provides methods for writing to buffers and textures
-    queue: wgpu::Queue,
-    sc_desc: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
-    size: winit::dpi::PhysicalSize<u32>,
-    render_pipeline: wgpu::RenderPipeline,
-    render_pipeline_2: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    num_vertices: u32,
-}
-
-impl State {
-    async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-
-        // The first thing to create for wgpu is an instance
-        // BackendBit::Primary => the backend with primary tier of support, like vulkan, dx12, etc.
-        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
-        // Primary use of an instance is to create `Surfaces` and `Adapters`
-        // This creates a surface from a "raw window handle", provided by winit
-        let surface = unsafe { instance.create_surface(window) };
-        // The adapter is a handle to the actual GPU
-        // We request it with certain "hard" (mandatory) and "soft" (priority) query options
-        let adapter = instance
-            .request_adapter(
-                // This is creating a type (struct is a category of type) from the wgpu library
-                &wgpu::RequestAdapterOptions {
-                    // Power preference default
-                    // ff//df
-                    power_preference: wgpu::PowerPreference::default(),
-                    // Make sure that the GPU can actually display stuff on the surface that we made using the wgpu instance ealier
-                    compatible_surface: Some(&surface),
-                },
-            )
-            .await
-            .unwrap();
-        // The adapter that we made earlier is now being used to create the `device` and `queue`
-        // `adapter.request_device()` opens an actual connection to the GPU, returning the `queue` and `device`
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    // `Features` field on `DeviceDescriptor` describes the features that we want
-                    // The features themselves can be device-specific and thus not cross-platform, care
-                    features: wgpu::Features::empty(),
-                    // Limitations of a certain device or adapter
-                    limits: wgpu::Limits::default(),
-                    // "Debug label for device"
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
-
-        // A swap chain descriptor describes the characteristics of a swap chain
-        let sc_desc = wgpu::SwapChainDescriptor {
-            // How will the swap chain be used? (Only option is RENDER_ATTACHMENT), which outputs texture to the screen
-            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
-            // How the textures are formatted in the swap chain, different displays have different formats,
-            // we can call an adaptor func to get the right texture format
-            format: adapter.get_swap_chain_preferred_format(&surface),
-            // Width and height of the swap chain, which are the width and height of the surface, (in this case a `window`)
-            width: size.width,
-            height: size.height,
-            // The mode that the swap chain will be presented in
-            // Uses the enum `PresentMode`, which has options of `FIFO` (vsync), `Immediate` (vsync-off) and `Mailbox` (hybrid)
-            present_mode: wgpu::PresentMode::Fifo,
-        };
-        // Represents the image or series of images that will be drawn onto a `Surface`
-        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-
-        // `wgpu::include_spirv!` differs from `wgpu::util::make_spirv` in that it takes in file name vs. `&str`
-        // So we can directly include our `.spv` files
-        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
-        let vs2_module = device.create_shader_module(&wgpu::include_spirv!("shader2.vert.spv"));
-        let fs_module = device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
-        let fs2_module = device.create_shader_module(&wgpu::include_spirv!("shader2.frag.spv"));
-
-        // Pipeline layout describes a pipeline
-        let render_pipeline_layout =
-            // `PipelineLayoutDescriptor` can be used to create a pipeline layout
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            // Label shows up in debuggin
-            label: Some("Render Pipeline"),
-            // TODO describes **bindings** for layout???
-            layout: Some(&render_pipeline_layout),
-            // Specifies the compiled shader module to use for this state
-            vertex: wgpu::VertexState {
-                // Shader module is a compiled shader module on the gpu that defines the rendering stage
-                // In this case we're inputting the vertex shader that we compiled
-                module: &vs_module,
-                // The entry point is the function that is called inside the GLSL shader.
-                // You could change the entry point here but make sure to rename the function in GLSL as well
-                entry_point: "main",
-                // The types of vertices that we want to pass to the vertex shader
-                buffers: &[Vertex::desc()],
-            },
-            // Fragment shader technically optional, so surrounded with `Some`
-            // The shader itself stores color in the swap chain
-            fragment: Some(wgpu::FragmentState {
-                // Inputting the fragment shader the we compiled earlier
-                module: &fs_module,
-                entry_point: "main",
-                // Describes how colors are stored and processed throughout the render pipeline
-                targets: &[wgpu::ColorTargetState {
-                    // We set the format to the `swap_chain` format so it's easy to copy to it
-                    format: sc_desc.format,
-                    // Just replace previous pixels
-                    color_blend: wgpu::BlendState::REPLACE,
-                    // Replace transparency?
-                    alpha_blend: wgpu::BlendState::REPLACE,
-                    //  Enables all color channels (RGBA) to be written to
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-            // Rasterization process for the pipeline
-            // Describes how to process primitives before they are sent to the fragment shader
-            // /
-            // How to interpret vertices when converting them into triangles
-            primitive: wgpu::PrimitiveState {
-                // A rasterizer (aster for star, starshaped) basically turns a vector into pixels
-                //
-                // 3 vertices = 1 triangle
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                // Vertices with counterclockwise order are considered the front face, used for right handed coordinate systems
-                front_face: wgpu::FrontFace::Ccw,
-                // Primitives that don't meet the criteria are culled, which is good because it speeds up rendering process for images that arent't seen anyway
-                cull_mode: wgpu::CullMode::Back,
-                polygon_mode: wgpu::PolygonMode::Fill,
-            },
-            // Depth / stencil buffer
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                // Samples calculated per pixel (MSAA)
-                count: 1,
-                // Enable all samples
-                mask: !0,
-                // Anti-aliasing
-                alpha_to_coverage_enabled: false,
-            },
-        });
-
-        let render_pipeline_2 = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            // Label shows up in debuggin
-            label: Some("Render Pipeline"),
-            // TODO describes **bindings** for layout???
-            layout: Some(&render_pipeline_layout),
-            // Specifies the compiled shader module to use for this state
-            vertex: wgpu::VertexState {
-                // Shader module is a compiled shader module on the gpu that defines the rendering stage
-                // In this case we're inputting the vertex shader that we compiled
-                module: &vs2_module,
-                // The entry point is the function that is called inside the GLSL shader.
-                // You could change the entry point here but make sure to rename the function in GLSL as well
-                entry_point: "main",
-                // The types of vertices that we want to pass to the vertex shader
-                buffers: &[Vertex::desc()],
-            },
-            // Fragment shader technically optional, so surrounded with `Some`
-            // The shader itself stores color in the swap chain
-            fragment: Some(wgpu::FragmentState {
-                // Inputting the fragment shader the we compiled earlier
-                module: &fs2_module,
-                entry_point: "main",
-                // Describes how colors are stored and processed throughout the render pipeline
-                targets: &[wgpu::ColorTargetState {
-                    // We set the format to the `swap_chain` format so it's easy to copy to it
-                    format: sc_desc.format,
-                    // Just replace previous pixels
-                    color_blend: wgpu::BlendState::REPLACE,
-                    // Replace transparency?
-                    alpha_blend: wgpu::BlendState::REPLACE,
-                    //  Enables all color channels (RGBA) to be written to
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-            // Rasterization process for the pipeline
-            // Describes how to process primitives before they are sent to the fragment shader
-            // /
-            // How to interpret vertices when converting them into triangles
-            primitive: wgpu::PrimitiveState {
-                // A rasterizer (aster for star, starshaped) basically turns a vector into pixels
-                //
-                // 3 vertices = 1 triangle
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                // Vertices with counterclockwise order are considered the front face, used for right handed coordinate systems
-                front_face: wgpu::FrontFace::Ccw,
-                // Primitives that don't meet the criteria are culled, which is good because it speeds up rendering process for images that arent't seen anyway
-                cull_mode: wgpu::CullMode::Back,
-                polygon_mode: wgpu::PolygonMode::Fill,
-            },
-            // Depth / stencil buffer
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                // Samples calculated per pixel (MSAA)
-                count: 1,
-                // Enable all samples
-                mask: !0,
-                // Anti-aliasing
-                alpha_to_coverage_enabled: false,
-            },
-        });
-
-        // `device.create_buffer_init()` comes from `use wgpu::util::DeviceExt;`
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            // Tries to cast `VERTICES` to `&[u8]`
-            contents: bytemuck::cast_slice(VERTICES),
-            // Usage of the buffer, in this case acting like a vertex buffer while drawing
-            usage: wgpu::BufferUsage::VERTEX,
-        });
-        let num_vertices = VERTICES.len() as u32;
-
-        // We can return the struct that can be built using all of our variables
-        Self {
-            surface,
-            device,
-            queue,
-            sc_desc,
-            swap_chain,
-            size,
-            render_pipeline,
-            vertex_buffer,
-            // TROLL
-            render_pipeline_2,
-            num_vertices,
-        }
-    }
-
-    // To allow window resizing, we need to recreate the swap chain with the new size
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        // Update current stored size with new size of resized window
-        self.size = new_size;
-        // Then update size of window in the swap chain descriptor
-        self.sc_desc.width = new_size.width;
-        self.sc_desc.height = new_size.height;
-        // Then create a new swap chain based on the updated swap chain descriptor size
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
-    }
-    // Checks if an event is fully complete, returns bool, if true, main won't process it any longer
-    fn input(&mut self, _event: &WindowEvent) -> bool {
-        false
-    }
-    // Nothing to update / "tick" for now
-    fn update(&mut self) {}
-    // Basically wgpu
-    fn render(&mut self, batch: &Batch) -> Result<(), wgpu::SwapChainError> {
-        // We need to get a frame to render to at first
-        // Includes `wgpu::Texture` and `wgpu::Textureview` that holds the image that is being drawn
-        // Remember the `?` operator here means return `Some(thing)` or return `Error`
-        let frame = self.swap_chain.get_current_frame()?.output;
-        // Recall that the `device` is responsible for creating commands to be sent to the `queue` of the GPU
-        // `encoder` builds this command buffer that is sent to GPU
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-        // The outside `{}` means that the reference to the mutable borrow of `encoder` in `encoder.begin_render_pass()` is dropped after so we're allowed to call `encoder.finish()` after
-        // You can also use `drop(render_pass)`
-        {
-            // Create a render pass using the encoder
-            // `RenderPassDescriptor` only has two fields, `color_attachments` and `depth_stencil_attachment`
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                // Describe where the color is going to be drawn to
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    // Informs the texture to which the colors are going to be saved to
-                    // Specifying the `frame.view` that we made earlier means we're drawing to the screen
-                    attachment: &frame.view,
-                    // The texture that will receive the resolved output, same as `attachment` unless multisampling (MSAA) is enabled
-                    resolve_target: None,
-                    // What to do with colors on the screen?
-                    ops: wgpu::Operations {
-                        // How to handle colors stored from the previous frame
-                        // Currently we're just clearing the colors
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            // Rgb based off of batch TROLL
-                            r: batch.cursor_position.0 % 1.0,
-                            g: batch.cursor_position.1 % 1.0,
-                            b: (batch.cursor_position.0 + batch.cursor_position.1) % 1.0,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                // I think that depth has to do with the z-index of pixels, and stenciling can block pixels?
-                // Maybe important for 3D but useless for 2D
-                depth_stencil_attachment: None,
-            });
-
-            // Set render pipeline to the pipeline that we defined in `state`
-            // if batch.space_pressed {
-            //     render_pass.set_pipeline(&self.render_pipeline_2);
-            // } else {
-            //     render_pass.set_pipeline(&self.render_pipeline);
-            // }
-            render_pass.set_pipeline(&self.render_pipeline);
-
-            // Assign portion of vertex buffer to a slot
-            // calls to `draw` will then use this vertex buffer
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            // Draw based on the vertex buffer vertices obv
-            render_pass.draw(0..self.num_vertices, 0..1);
-        }
-        println!("hihihi");
-        // Queue accepts anything that implements IntoIter
-        self.queue.submit(std::iter::once(encoder.finish()));
-        println!("hihihi");
+// Frame rate of the recordings, in simulated frames per second
+const RECORDING_FPS: u32 = 60;
 
-        Ok(())
+fn toggle_recording(state: &mut State, output: RecordingOutput) {
+    let result = if state.is_recording() {
+        state
+            .stop_recording()
+            .map(|frames| println!("Stopped recording after {} frames", frames))
+    } else {
+        state
+            .start_recording(output, RECORDING_FPS)
+            .map(|_| println!("Started recording"))
+    };
+    if let Err(e) = result {
+        eprintln!("Recording failed: {}", e);
     }
 }
@@ -0,0 +1,143 @@
+// A vertex buffer, optionally with an index buffer so that shared vertices only have to be stored once
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub num_vertices: u32,
+    index: Option<IndexBuffer>,
+}
+
+struct IndexBuffer {
+    buffer: wgpu::Buffer,
+    format: wgpu::IndexFormat,
+    num_indices: u32,
+}
+
+// An index buffer that doesn't fit the vertices it's made for
+#[derive(Debug)]
+pub enum MeshError {
+    // `indices[position]` points past the last vertex
+    IndexOutOfRange {
+        position: usize,
+        index: u32,
+        num_vertices: usize,
+    },
+}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::IndexOutOfRange {
+                position,
+                index,
+                num_vertices,
+            } => write!(
+                f,
+                "index {} (at position {}) is out of range, the mesh only has {} vertices",
+                index, position, num_vertices
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+// 16 bit indices take up half the space, but can only point at the first 65535 vertices
+// Index 65535 (`0xFFFF`) is left out, since that's the value that restarts a strip instead of pointing at a vertex
+pub fn index_format_for(num_vertices: usize) -> wgpu::IndexFormat {
+    if num_vertices <= u16::MAX as usize {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
+}
+
+impl Mesh {
+    // A mesh that draws `vertices` in order, every 3 of them making a triangle
+    pub fn new<V: bytemuck::Pod>(device: &wgpu::Device, label: &str, vertices: &[V]) -> Self {
+        Self {
+            vertex_buffer: Self::create_vertex_buffer(device, label, vertices),
+            num_vertices: vertices.len() as u32,
+            index: None,
+        }
+    }
+
+    // A mesh that draws the vertices that `indices` point at, every 3 indices making a triangle
+    // The index format is picked from the number of vertices, so `indices` can always be given as `u32`s
+    // Every index has to point at one of `vertices`, otherwise it would get cut down to 16 bits or read past the buffer
+    pub fn indexed<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Result<Self, MeshError> {
+        if let Some((position, &index)) = indices
+            .iter()
+            .enumerate()
+            .find(|(_, &index)| index as usize >= vertices.len())
+        {
+            return Err(MeshError::IndexOutOfRange {
+                position,
+                index,
+                num_vertices: vertices.len(),
+            });
+        }
+        let format = index_format_for(vertices.len());
+        let index_label = format!("{} Index Buffer", label);
+        let contents = match format {
+            wgpu::IndexFormat::Uint16 => {
+                let indices = indices.iter().map(|&i| i as u16).collect::<Vec<u16>>();
+                bytemuck::cast_slice(&indices).to_vec()
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&index_label),
+            contents: &contents,
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        Ok(Self {
+            vertex_buffer: Self::create_vertex_buffer(device, label, vertices),
+            num_vertices: vertices.len() as u32,
+            index: Some(IndexBuffer {
+                buffer,
+                format,
+                num_indices: indices.len() as u32,
+            }),
+        })
+    }
+
+    fn create_vertex_buffer<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        label: &str,
+        vertices: &[V],
+    ) -> wgpu::Buffer {
+        let vertex_label = format!("{} Vertex Buffer", label);
+        // `device.create_buffer_init()` comes from `use wgpu::util::DeviceExt;`
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&vertex_label),
+            // Tries to cast the vertices to `&[u8]`
+            contents: bytemuck::cast_slice(vertices),
+            // Usage of the buffer, in this case acting like a vertex buffer while drawing
+            usage: wgpu::BufferUsage::VERTEX,
+        })
+    }
+
+    pub fn index_format(&self) -> Option<wgpu::IndexFormat> {
+        self.index.as_ref().map(|index| index.format)
+    }
+
+    // Binds the buffers to slot 0 (and the index buffer) and draws the whole mesh
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index {
+            Some(index) => {
+                render_pass.set_index_buffer(index.buffer.slice(..), index.format);
+                render_pass.draw_indexed(0..index.num_indices, 0, instances);
+            }
+            None => render_pass.draw(0..self.num_vertices, instances),
+        }
+    }
+}
@@ -0,0 +1,619 @@
+// Building render pipelines without writing out a 70 line `RenderPipelineDescriptor` every time
+use crate::reflect::{EntryPointInterface, VaryingType};
+use crate::shader::{entry_point_module_name, LoadedShader, ShaderLibrary};
+use crate::texture::{DEPTH_FORMAT, SAMPLE_COUNTS};
+
+// Vertex strides have to be a multiple of this, same as `wgpu_types::VERTEX_STRIDE_ALIGNMENT` (which `wgpu` doesn't re-export)
+const VERTEX_STRIDE_ALIGNMENT: wgpu::BufferAddress = 4;
+
+// Everything that can be wrong with a pipeline before we hand it to wgpu
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineError {
+    // Every pipeline needs a vertex shader
+    NoVertexShader,
+    // The shader isn't in the `ShaderLibrary`
+    MissingShader(String),
+    // Two attributes (possibly in different buffers) want the same `layout(location=N)`
+    DuplicateLocation(u32),
+    // The attribute at this location goes past the end of its vertex
+    AttributeOutOfBounds {
+        location: u32,
+        end: wgpu::BufferAddress,
+        array_stride: wgpu::BufferAddress,
+    },
+    // Attribute offsets have to be a multiple of 4
+    UnalignedOffset {
+        location: u32,
+        offset: wgpu::BufferAddress,
+    },
+    // Vertex strides have to be a multiple of `VERTEX_STRIDE_ALIGNMENT`
+    UnalignedStride {
+        buffer: usize,
+        array_stride: wgpu::BufferAddress,
+    },
+    // The shader doesn't have a function called `entry_point`
+    MissingEntryPoint {
+        shader: String,
+        entry_point: String,
+    },
+    // The vertex shader reads a location that none of the vertex buffers have an attribute for
+    MissingAttribute {
+        shader: String,
+        location: u32,
+        expected: VaryingType,
+    },
+    // The attribute is e.g. a float, but the vertex shader reads it as an int
+    AttributeTypeMismatch {
+        shader: String,
+        location: u32,
+        format: wgpu::VertexFormat,
+        expected: VaryingType,
+    },
+    // The fragment shader reads a location that the vertex shader never writes
+    MissingVarying {
+        vertex_shader: String,
+        fragment_shader: String,
+        location: u32,
+        expected: VaryingType,
+    },
+    // Both shaders use the location, but not with the same type
+    VaryingTypeMismatch {
+        vertex_shader: String,
+        fragment_shader: String,
+        location: u32,
+        output: VaryingType,
+        input: VaryingType,
+    },
+    // The pipeline depth tests against some other format than `State`'s depth buffer, which is `DEPTH_FORMAT`
+    DepthFormat(wgpu::TextureFormat),
+    // MSAA can only be one of `SAMPLE_COUNTS` samples per pixel
+    InvalidSampleCount(u32),
+    // The adapter can't draw with that many samples per pixel, along with what wgpu said about it
+    UnsupportedSampleCount {
+        count: u32,
+        errors: String,
+    },
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::NoVertexShader => write!(f, "pipeline has no vertex shader"),
+            PipelineError::MissingShader(name) => write!(f, "no shader named `{}`", name),
+            PipelineError::DuplicateLocation(location) => {
+                write!(f, "more than one attribute uses location {}", location)
+            }
+            PipelineError::AttributeOutOfBounds {
+                location,
+                end,
+                array_stride,
+            } => write!(
+                f,
+                "attribute at location {} ends at byte {}, past the array stride of {}",
+                location, end, array_stride
+            ),
+            PipelineError::UnalignedOffset { location, offset } => write!(
+                f,
+                "attribute at location {} has offset {}, which isn't a multiple of 4",
+                location, offset
+            ),
+            PipelineError::UnalignedStride {
+                buffer,
+                array_stride,
+            } => write!(
+                f,
+                "vertex buffer {} has array stride {}, which isn't a multiple of {}",
+                buffer, array_stride, VERTEX_STRIDE_ALIGNMENT
+            ),
+            PipelineError::MissingEntryPoint {
+                shader,
+                entry_point,
+            } => write!(
+                f,
+                "`{}` has no entry point called `{}`",
+                shader, entry_point
+            ),
+            PipelineError::MissingAttribute {
+                shader,
+                location,
+                expected,
+            } => write!(
+                f,
+                "`{}` reads a {} from location {}, but no vertex buffer has an attribute there",
+                shader, expected, location
+            ),
+            PipelineError::AttributeTypeMismatch {
+                shader,
+                location,
+                format,
+                expected,
+            } => write!(
+                f,
+                "`{}` reads a {} from location {}, but the vertex attribute there is {:?}",
+                shader, expected, location, format
+            ),
+            PipelineError::MissingVarying {
+                vertex_shader,
+                fragment_shader,
+                location,
+                expected,
+            } => write!(
+                f,
+                "`{}` reads a {} from location {}, but `{}` doesn't write anything there",
+                fragment_shader, expected, location, vertex_shader
+            ),
+            PipelineError::VaryingTypeMismatch {
+                vertex_shader,
+                fragment_shader,
+                location,
+                output,
+                input,
+            } => write!(
+                f,
+                "`{}` writes a {} to location {}, but `{}` reads it as a {}",
+                vertex_shader, output, location, fragment_shader, input
+            ),
+            PipelineError::DepthFormat(format) => write!(
+                f,
+                "pipeline depth tests against a {:?} depth buffer, but the depth buffer is {:?}",
+                format, DEPTH_FORMAT
+            ),
+            PipelineError::InvalidSampleCount(count) => write!(
+                f,
+                "sample count has to be one of {:?}, not {}",
+                SAMPLE_COUNTS, count
+            ),
+            PipelineError::UnsupportedSampleCount { count, errors } => write!(
+                f,
+                "the adapter can't draw with {} samples per pixel: {}",
+                count, errors
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+// Makes sure that the vertex buffer layouts make sense on their own, before wgpu gets to see them
+pub fn validate_vertex_layouts(buffers: &[wgpu::VertexBufferLayout]) -> Result<(), PipelineError> {
+    let mut locations = std::collections::HashSet::new();
+    for (index, buffer) in buffers.iter().enumerate() {
+        if buffer.array_stride % VERTEX_STRIDE_ALIGNMENT != 0 {
+            return Err(PipelineError::UnalignedStride {
+                buffer: index,
+                array_stride: buffer.array_stride,
+            });
+        }
+        for attribute in buffer.attributes {
+            if !locations.insert(attribute.shader_location) {
+                return Err(PipelineError::DuplicateLocation(attribute.shader_location));
+            }
+            if attribute.offset % 4 != 0 {
+                return Err(PipelineError::UnalignedOffset {
+                    location: attribute.shader_location,
+                    offset: attribute.offset,
+                });
+            }
+            let end = attribute.offset + attribute.format.size();
+            if end > buffer.array_stride {
+                return Err(PipelineError::AttributeOutOfBounds {
+                    location: attribute.shader_location,
+                    end,
+                    array_stride: buffer.array_stride,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Makes sure that every location the vertex shader reads comes from an attribute of the same kind (float, int, uint)
+// The number of components doesn't have to match, missing ones are filled in with 0 (or 1 for `w`) and extra ones are ignored
+// Attributes that the shader doesn't read are fine too, e.g. a shader that ignores the vertex colors
+pub fn validate_vertex_inputs(
+    shader: &str,
+    interface: &EntryPointInterface,
+    buffers: &[wgpu::VertexBufferLayout],
+) -> Result<(), PipelineError> {
+    for input in &interface.inputs {
+        let attribute = buffers
+            .iter()
+            .flat_map(|buffer| buffer.attributes)
+            .find(|attribute| attribute.shader_location == input.location)
+            .ok_or_else(|| PipelineError::MissingAttribute {
+                shader: shader.to_string(),
+                location: input.location,
+                expected: input.ty,
+            })?;
+        if VaryingType::of_vertex_format(attribute.format).kind != input.ty.kind {
+            return Err(PipelineError::AttributeTypeMismatch {
+                shader: shader.to_string(),
+                location: input.location,
+                format: attribute.format,
+                expected: input.ty,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Makes sure that every location the fragment shader reads is written by the vertex shader, with the same type
+// The fragment shader is allowed to read fewer components than were written, e.g. `vec3` out of a `vec4`
+pub fn validate_varyings(
+    (vertex_shader, vertex): (&str, &EntryPointInterface),
+    (fragment_shader, fragment): (&str, &EntryPointInterface),
+) -> Result<(), PipelineError> {
+    for input in &fragment.inputs {
+        let output =
+            vertex
+                .output(input.location)
+                .ok_or_else(|| PipelineError::MissingVarying {
+                    vertex_shader: vertex_shader.to_string(),
+                    fragment_shader: fragment_shader.to_string(),
+                    location: input.location,
+                    expected: input.ty,
+                })?;
+        if output.kind != input.ty.kind || output.components < input.ty.components {
+            return Err(PipelineError::VaryingTypeMismatch {
+                vertex_shader: vertex_shader.to_string(),
+                fragment_shader: fragment_shader.to_string(),
+                location: input.location,
+                output,
+                input: input.ty,
+            });
+        }
+    }
+    Ok(())
+}
+
+// What the entry point of `shader`, which is called `name`, reads and writes
+// `None` if the shader couldn't be reflected, in which case there's nothing to check it against
+fn entry_point_interface<'s>(
+    shader: &'s LoadedShader,
+    name: &str,
+    entry_point: &str,
+) -> Result<Option<&'s EntryPointInterface>, PipelineError> {
+    match &shader.interface {
+        Some(interface) => interface.entry_point(entry_point).map(Some).ok_or_else(|| {
+            PipelineError::MissingEntryPoint {
+                shader: name.to_string(),
+                entry_point: entry_point.to_string(),
+            }
+        }),
+        None => Ok(None),
+    }
+}
+
+// Builds a `wgpu::RenderPipeline` out of shader names from a `ShaderLibrary`
+// Anything that isn't set uses the defaults from `new`
+// A builder without a layout can be kept around and cloned later to rebuild the pipeline, e.g. after a shader was reloaded
+#[derive(Clone)]
+pub struct RenderPipelineBuilder<'a> {
+    label: &'a str,
+    layout: Option<&'a wgpu::PipelineLayout>,
+    vertex_shader: Option<&'a str>,
+    fragment_shader: Option<&'a str>,
+    // The entry point is the function that is called inside the shader, `main` unless the manifest gives others
+    vertex_entry_point: &'a str,
+    fragment_entry_point: &'a str,
+    // The types of vertices that we want to pass to the vertex shader
+    vertex_buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    // Describes how colors are stored and processed throughout the render pipeline
+    color_targets: Vec<wgpu::ColorTargetState>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+}
+
+impl<'a> RenderPipelineBuilder<'a> {
+    pub fn new(label: &'a str) -> Self {
+        Self {
+            label,
+            layout: None,
+            vertex_shader: None,
+            fragment_shader: None,
+            vertex_entry_point: "main",
+            fragment_entry_point: "main",
+            vertex_buffers: Vec::new(),
+            color_targets: Vec::new(),
+            // How to interpret vertices when converting them into triangles
+            primitive: wgpu::PrimitiveState {
+                // 3 vertices = 1 triangle
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                // Vertices with counterclockwise order are considered the front face, used for right handed coordinate systems
+                front_face: wgpu::FrontFace::Ccw,
+                // Triangles facing away from us are never seen anyway, so don't bother drawing them
+                cull_mode: wgpu::CullMode::Back,
+                polygon_mode: wgpu::PolygonMode::Fill,
+            },
+            // No depth / stencil buffer
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                // Samples calculated per pixel (MSAA), 1 means no MSAA
+                count: 1,
+                // Enable all samples
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+
+    // Describes the bind groups that the shaders use
+    pub fn layout(mut self, layout: &'a wgpu::PipelineLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    // Name of the vertex shader in the `ShaderLibrary`, e.g. `"shader.vert"`
+    pub fn vertex_shader(mut self, name: &'a str) -> Self {
+        self.vertex_shader = Some(name);
+        self
+    }
+
+    // Name of the fragment shader in the `ShaderLibrary`, e.g. `"shader.frag"`
+    pub fn fragment_shader(mut self, name: &'a str) -> Self {
+        self.fragment_shader = Some(name);
+        self
+    }
+
+    // Needed when the vertex function isn't called `main`, e.g. WGSL files that have both stages in them
+    // For GLSL shaders with several entry points, this also picks which of their modules gets used
+    pub fn vertex_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.vertex_entry_point = entry_point;
+        self
+    }
+
+    pub fn fragment_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.fragment_entry_point = entry_point;
+        self
+    }
+
+    // Adds a vertex buffer, in the order that they're bound with `set_vertex_buffer`
+    pub fn vertex_buffer(mut self, layout: wgpu::VertexBufferLayout<'a>) -> Self {
+        self.vertex_buffers.push(layout);
+        self
+    }
+
+    // Adds a color target that just replaces the previous pixels and writes to all of the channels
+    pub fn color_target(self, format: wgpu::TextureFormat) -> Self {
+        self.color_target_state(wgpu::ColorTargetState {
+            format,
+            color_blend: wgpu::BlendState::REPLACE,
+            alpha_blend: wgpu::BlendState::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        })
+    }
+
+    // Adds a color target with custom blending or write mask
+    pub fn color_target_state(mut self, state: wgpu::ColorTargetState) -> Self {
+        self.color_targets.push(state);
+        self
+    }
+
+    pub fn primitive(mut self, primitive: wgpu::PrimitiveState) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: wgpu::CullMode) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: wgpu::DepthStencilState) -> Self {
+        self.depth_stencil = Some(depth_stencil);
+        self
+    }
+
+    // Only draws the pixels that pass `compare` against the depth buffer, and writes their depth to it
+    // e.g. `Less` keeps whatever is closest, no matter what order things are drawn in
+    pub fn depth_test(self, compare: wgpu::CompareFunction) -> Self {
+        self.depth_stencil(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+            clamp_depth: false,
+        })
+    }
+
+    // The format of the depth buffer that the pipeline needs, `None` if it doesn't use one
+    pub fn depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_stencil
+            .as_ref()
+            .map(|depth_stencil| depth_stencil.format)
+    }
+
+    pub fn multisample(mut self, multisample: wgpu::MultisampleState) -> Self {
+        self.multisample = multisample;
+        self
+    }
+
+    // `State::add_pipeline` overrides this with `State::sample_count`, the pipeline has to match the attachments it draws to
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.multisample.count = count;
+        self
+    }
+
+    // Whether the pipeline uses the shader called `name`, or the module of one of its entry points
+    pub fn uses_shader(&self, name: &str) -> bool {
+        let uses = |shader: Option<&str>, entry_point| match shader {
+            Some(shader) => shader == name || entry_point_module_name(shader, entry_point) == name,
+            None => false,
+        };
+        uses(self.vertex_shader, self.vertex_entry_point)
+            || uses(self.fragment_shader, self.fragment_entry_point)
+    }
+
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderLibrary,
+    ) -> Result<wgpu::RenderPipeline, PipelineError> {
+        // GLSL shaders with several entry points have a module for each of them
+        let lookup = |name: &str, entry_point| {
+            shaders
+                .entry_point(name, entry_point)
+                .ok_or_else(|| PipelineError::MissingShader(name.to_string()))
+        };
+        let vertex_shader = self.vertex_shader.ok_or(PipelineError::NoVertexShader)?;
+        // From here on the entry points go by what they're called inside of their modules, see `ShaderLibrary::entry_point`
+        let (vertex_loaded, vertex_entry_point) = lookup(vertex_shader, self.vertex_entry_point)?;
+        // Fragment shader technically optional, so surrounded with `Some`
+        let fragment_loaded = self
+            .fragment_shader
+            .map(|name| lookup(name, self.fragment_entry_point))
+            .transpose()?;
+
+        validate_vertex_layouts(&self.vertex_buffers)?;
+
+        // Then whether the shaders agree with the vertex buffers and with each other
+        let vertex = entry_point_interface(vertex_loaded, vertex_shader, vertex_entry_point)?;
+        if let Some(vertex) = vertex {
+            validate_vertex_inputs(vertex_shader, vertex, &self.vertex_buffers)?;
+        }
+        if let (Some(fragment_shader), Some((fragment_loaded, fragment_entry_point))) =
+            (self.fragment_shader, fragment_loaded)
+        {
+            let fragment =
+                entry_point_interface(fragment_loaded, fragment_shader, fragment_entry_point)?;
+            if let (Some(vertex), Some(fragment)) = (vertex, fragment) {
+                validate_varyings((vertex_shader, vertex), (fragment_shader, fragment))?;
+            }
+        }
+
+        Ok(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(self.label),
+                layout: self.layout,
+                vertex: wgpu::VertexState {
+                    module: &vertex_loaded.module,
+                    entry_point: vertex_entry_point,
+                    buffers: &self.vertex_buffers,
+                },
+                fragment: fragment_loaded.map(|(shader, entry_point)| wgpu::FragmentState {
+                    module: &shader.module,
+                    entry_point,
+                    targets: &self.color_targets,
+                }),
+                primitive: self.primitive.clone(),
+                depth_stencil: self.depth_stencil.clone(),
+                multisample: self.multisample.clone(),
+            }),
+        )
+    }
+}
+
+// Where a pipeline's vertices come from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexSource {
+    // The `Vertex` buffer is bound to slot 0 and every vertex in it gets drawn
+    VertexBuffer,
+    // A square of `TexturedVertex`es is bound to slot 0 instead, for drawing `State`'s texture
+    TexturedQuad,
+    // The vertex shader makes up its own vertices from `gl_VertexIndex`, so no buffer is bound
+    Procedural { vertex_count: u32 },
+    // Every `InstancedMesh` in `State` gets drawn, with its vertices in slot 0 and its `Instance`s in slot 1
+    Instanced,
+}
+
+// A pipeline, together with what it needs to be drawn and rebuilt
+pub struct RegisteredPipeline {
+    pub name: String,
+    pub pipeline: wgpu::RenderPipeline,
+    pub vertex_source: VertexSource,
+    // What the pipeline was built from, minus the layout
+    pub builder: RenderPipelineBuilder<'static>,
+}
+
+// All of the pipelines that can be drawn with, in the order that they were registered
+// `Batch::active_pipeline` is an index into this
+pub struct PipelineRegistry {
+    entries: Vec<RegisteredPipeline>,
+}
+
+impl PipelineRegistry {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    // Adds a pipeline, or replaces the one that already has the same name (keeping its index)
+    // Returns the index of the pipeline
+    pub fn register(
+        &mut self,
+        name: &str,
+        builder: RenderPipelineBuilder<'static>,
+        pipeline: wgpu::RenderPipeline,
+        vertex_source: VertexSource,
+    ) -> usize {
+        let entry = RegisteredPipeline {
+            name: name.to_string(),
+            pipeline,
+            vertex_source,
+            builder,
+        };
+        match self.index_of(name) {
+            Some(index) => {
+                self.entries[index] = entry;
+                index
+            }
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Indices past the end wrap back around to the start, so cycling can never select a pipeline that doesn't exist
+    pub fn get(&self, index: usize) -> Option<&RegisteredPipeline> {
+        if self.entries.is_empty() {
+            None
+        } else {
+            self.entries.get(index % self.entries.len())
+        }
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.name == name)
+    }
+
+    // The index after `index`, wrapping back to the first pipeline after the last one
+    pub fn next_index(&self, index: usize) -> usize {
+        if self.entries.is_empty() {
+            0
+        } else {
+            (index + 1) % self.entries.len()
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut RegisteredPipeline> {
+        self.entries.iter_mut()
+    }
+}
+
+impl Default for PipelineRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
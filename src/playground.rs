@@ -0,0 +1,70 @@
+// A Shadertoy style playground, a fullscreen triangle with a fragment shader loaded from a file
+//
+// The fragment shader is written the same way as on https://www.shadertoy.com, it only has to define
+//
+// void mainImage(out vec4 fragColor, in vec2 fragCoord) { ... }
+//
+// and can use `iResolution`, `iTime`, `iMouse` and `iFrame`
+// We wrap it with the `Globals` block and a `main` that calls `mainImage`, then compile it with shaderc
+use crate::shader::{self, BuiltinShader, CompiledShader, ShaderError};
+use crate::Batch;
+use std::path::Path;
+
+// Name of the playground pipeline in the `PipelineRegistry`, and of its fragment shader in the `ShaderLibrary`
+pub const PIPELINE_NAME: &str = "playground";
+pub const FRAGMENT_SHADER_NAME: &str = "playground.frag";
+// The vertex shader that the playground draws its fullscreen triangle with
+pub const VERTEX_SHADER_NAME: &str = BuiltinShader::FullscreenVert.name();
+
+// Goes before the playground shader, maps the Shadertoy inputs onto our `Globals` (see `src/uniforms.rs`)
+const PRELUDE: &str = "#version 460
+
+#include <globals.glsl>
+
+layout(location=0) out vec4 f_color;
+
+#define iResolution vec3(u_resolution, 1.0)
+#define iTime u_time
+#define iMouse u_mouse
+#define iFrame int(u_frame)
+";
+
+// Goes after the playground shader
+const EPILOGUE: &str = "
+void main() {
+  // Shadertoy's origin is the bottom left corner, while `gl_FragCoord`'s is the top left
+  vec4 color;
+  mainImage(color, vec2(gl_FragCoord.x, u_resolution.y - gl_FragCoord.y));
+  // Shadertoy ignores alpha, so we do too
+  f_color = vec4(color.rgb, 1.0);
+}
+";
+
+// Wraps the body of a Shadertoy shader so that it becomes a complete GLSL fragment shader
+pub fn wrap_source(source: &str) -> String {
+    // `#line 1` makes the compiler errors point at the lines of the original file instead of the wrapped one
+    format!("{}#line 1\n{}\n{}", PRELUDE, source, EPILOGUE)
+}
+
+// Reads and compiles the playground shader at `path` into SPIR-V
+pub fn compile(path: &Path) -> Result<CompiledShader, ShaderError> {
+    let source = std::fs::read_to_string(path)?;
+    shader::compile_glsl(
+        &wrap_source(&source),
+        shaderc::ShaderKind::Fragment,
+        &path.display().to_string(),
+        "main",
+        &[],
+    )
+}
+
+// Works out Shadertoy's `iMouse` from the batch, for a frame that's `height` pixels tall
+// `xy` is the cursor position while the left button is held (and stays put once it's released)
+// `zw` is where the button was pressed, negated once it's released
+pub fn mouse(batch: &Batch, height: u32) -> [f32; 4] {
+    let flip = |(x, y): (f64, f64)| (x as f32, height as f32 - y as f32);
+    let (drag_x, drag_y) = flip(batch.drag_position);
+    let (click_x, click_y) = flip(batch.click_position);
+    let sign = if batch.mouse_pressed { 1.0 } else { -1.0 };
+    [drag_x, drag_y, sign * click_x, sign * click_y]
+}
@@ -0,0 +1,36 @@
+// `post/blur.frag`
+// A gaussian blur of the texture that `State` binds to group 1, split into a horizontal and a vertical pass
+// Both passes live in this one file, `src/shaders/manifest.toml` lists them as entry points so that each one gets its
+// own module, `post/blur.frag:blur_x` and `post/blur.frag:blur_y`, and pipelines pick one with `fragment_entry_point`
+// Meant to be drawn with `fullscreen.vert`, the texture gets stretched over the whole frame
+
+#version 460
+
+#include <globals.glsl>
+
+layout(set=1, binding=0) uniform texture2D t_diffuse;
+layout(set=1, binding=1) uniform sampler s_diffuse;
+
+layout(location=0) out vec4 f_color;
+
+// 5 taps of a gaussian, the middle one and then two on each side
+const float WEIGHTS[3] = float[](0.375, 0.25, 0.0625);
+
+// Blurs along `direction`, which is one texel long
+vec4 blur(vec2 direction) {
+  vec2 uv = gl_FragCoord.xy / u_resolution;
+  vec4 color = texture(sampler2D(t_diffuse, s_diffuse), uv) * WEIGHTS[0];
+  for (int i = 1; i < 3; i++) {
+    color += texture(sampler2D(t_diffuse, s_diffuse), uv + direction * float(i)) * WEIGHTS[i];
+    color += texture(sampler2D(t_diffuse, s_diffuse), uv - direction * float(i)) * WEIGHTS[i];
+  }
+  return color;
+}
+
+void blur_x() {
+  f_color = blur(vec2(1.0 / float(textureSize(sampler2D(t_diffuse, s_diffuse), 0).x), 0.0));
+}
+
+void blur_y() {
+  f_color = blur(vec2(0.0, 1.0 / float(textureSize(sampler2D(t_diffuse, s_diffuse), 0).y)));
+}
@@ -0,0 +1,209 @@
+// Recording every rendered frame to disk, either as numbered PNGs or as one uncompressed Y4M video
+// Frames are captured at a fixed simulated frame rate, so the output is the same no matter how fast the machine is
+use crate::capture::{self, BufferDimensions, CaptureError};
+use futures::FutureExt;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+
+// How many readback buffers we cycle through
+// While the GPU copies the newest frame into one buffer, the CPU can write out an older one, so we don't stall every frame
+const RING_SIZE: usize = 3;
+
+// Where the recording ends up
+pub enum RecordingOutput {
+    // Numbered PNGs inside of a directory, `frame-000000.png`, `frame-000001.png`, ...
+    ImageSequence(PathBuf),
+    // A single `.y4m` file, which ffmpeg and most video players can read
+    Y4m(PathBuf),
+}
+
+enum Sink {
+    ImageSequence(PathBuf),
+    Y4m(BufWriter<File>),
+}
+
+// The future returned by `BufferSlice::map_async`, boxed so that we can store it
+type Mapping = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    // The frame number that is being copied into `buffer`, and the future that resolves when we can read it
+    pending: Option<(u64, Mapping)>,
+}
+
+pub struct Recorder {
+    fps: u32,
+    format: wgpu::TextureFormat,
+    dimensions: BufferDimensions,
+    // Frames are drawn into this texture as well as the swap chain, since swap chain textures can't be copied from
+    texture: wgpu::Texture,
+    sink: Sink,
+    slots: Vec<Slot>,
+    // The slot that the next frame is going to be copied into
+    next_slot: usize,
+    frames_captured: u64,
+}
+
+impl Recorder {
+    // `texture` has to be a `width` x `height` texture of `format` with `RENDER_ATTACHMENT | COPY_SRC` usage
+    pub fn new(
+        device: &wgpu::Device,
+        texture: wgpu::Texture,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        output: RecordingOutput,
+        fps: u32,
+    ) -> Result<Self, CaptureError> {
+        let bytes_per_pixel =
+            capture::bytes_per_pixel(format).ok_or(CaptureError::UnsupportedFormat(format))?;
+        let dimensions = BufferDimensions::new(width, height, bytes_per_pixel);
+
+        let sink = match output {
+            RecordingOutput::ImageSequence(dir) => {
+                std::fs::create_dir_all(&dir)?;
+                Sink::ImageSequence(dir)
+            }
+            RecordingOutput::Y4m(path) => {
+                let mut file = BufWriter::new(File::create(path)?);
+                // `C444` means that the color planes are full size, so we don't have to worry about odd widths or heights
+                // `XCOLORRANGE=FULL` because players assume limited range otherwise, see `rgba_to_yuv444`
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444 XCOLORRANGE=FULL",
+                    width, height, fps
+                )?;
+                Sink::Y4m(file)
+            }
+        };
+
+        let slots = (0..RING_SIZE)
+            .map(|_| Slot {
+                buffer: capture::create_readback_buffer(device, &dimensions),
+                pending: None,
+            })
+            .collect();
+
+        Ok(Self {
+            fps,
+            format,
+            dimensions,
+            texture,
+            sink,
+            slots,
+            next_slot: 0,
+            frames_captured: 0,
+        })
+    }
+
+    // How much simulated time passes between two recorded frames
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps as f64)
+    }
+
+    pub fn frames_captured(&self) -> u64 {
+        self.frames_captured
+    }
+
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.texture
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    // Has to be called before `copy_frame`, makes sure that the next slot's buffer is free to be copied into
+    // If it still holds an older frame, we wait for that frame and write it out first
+    pub fn prepare_frame(&mut self, device: &wgpu::Device) -> Result<(), CaptureError> {
+        self.flush_slot(device, self.next_slot)
+    }
+
+    // Records the copy of this frame's texture into the next slot's buffer
+    pub fn copy_frame(&self, encoder: &mut wgpu::CommandEncoder) {
+        capture::copy_texture_to_buffer(
+            encoder,
+            &self.texture,
+            &self.slots[self.next_slot].buffer,
+            &self.dimensions,
+        );
+    }
+
+    // Has to be called after the commands from `copy_frame` have been submitted
+    // Starts mapping the buffer, but doesn't wait for it, that happens once the slot comes back around
+    pub fn frame_submitted(&mut self, device: &wgpu::Device) {
+        let slot = &mut self.slots[self.next_slot];
+        let mapping = slot.buffer.slice(..).map_async(wgpu::MapMode::Read);
+        slot.pending = Some((self.frames_captured, Box::pin(mapping)));
+        self.frames_captured += 1;
+        self.next_slot = (self.next_slot + 1) % RING_SIZE;
+        // Lets the GPU make progress on earlier mappings without blocking
+        device.poll(wgpu::Maintain::Poll);
+    }
+
+    // Writes out every frame that's still in flight, oldest first, and closes the output
+    // Returns how many frames were recorded
+    pub fn finish(mut self, device: &wgpu::Device) -> Result<u64, CaptureError> {
+        for i in 0..RING_SIZE {
+            self.flush_slot(device, (self.next_slot + i) % RING_SIZE)?;
+        }
+        if let Sink::Y4m(file) = &mut self.sink {
+            file.flush()?;
+        }
+        Ok(self.frames_captured)
+    }
+
+    fn flush_slot(&mut self, device: &wgpu::Device, index: usize) -> Result<(), CaptureError> {
+        let (frame, mut mapping) = match self.slots[index].pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        // Only the mappings of frames that the GPU already finished get resolved here, without waiting on anything
+        device.poll(wgpu::Maintain::Poll);
+        let mapped = match mapping.as_mut().now_or_never() {
+            Some(mapped) => mapped,
+            // This frame isn't done yet, which is the only time that we have to wait for the GPU
+            // wgpu can't wait for one submission, so this waits for everything that was submitted so far
+            None => {
+                device.poll(wgpu::Maintain::Wait);
+                futures::executor::block_on(mapping)
+            }
+        };
+        mapped?;
+        let pixels = capture::read_mapped_buffer(&self.slots[index].buffer, &self.dimensions);
+        self.write_frame(frame, pixels)
+    }
+
+    fn write_frame(&mut self, frame: u64, mut pixels: Vec<u8>) -> Result<(), CaptureError> {
+        match &mut self.sink {
+            Sink::ImageSequence(dir) => {
+                let path = dir.join(format!("frame-{:06}.png", frame));
+                capture::save_png(&path, &self.dimensions, self.format, pixels)
+            }
+            Sink::Y4m(file) => {
+                capture::convert_to_rgba8(self.format, &mut pixels)?;
+                file.write_all(b"FRAME\n")?;
+                file.write_all(&rgba_to_yuv444(&pixels))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+// Converts RGBA pixels into the three full size Y, U and V planes that a `C444` Y4M frame is made of
+// Uses the full range BT.601 (JPEG) coefficients, so 0 to 255 instead of 16 to 235, which the header says
+fn rgba_to_yuv444(pixels: &[u8]) -> Vec<u8> {
+    let count = pixels.len() / 4;
+    let mut planes = vec![0; count * 3];
+    for (i, pixel) in pixels.chunks(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 0.299 * r + 0.587 * g + 0.114 * b;
+        let u = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+        let v = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+        planes[i] = y.round().clamp(0.0, 255.0) as u8;
+        planes[count + i] = u.round().clamp(0.0, 255.0) as u8;
+        planes[count * 2 + i] = v.round().clamp(0.0, 255.0) as u8;
+    }
+    planes
+}
@@ -0,0 +1,388 @@
+// Working out what a shader reads and writes, so that pipelines can be checked before wgpu ever sees them
+//
+// A mismatch between `layout(location=N) in` and the vertex buffer layout, or between what the vertex shader writes and
+// what the fragment shader reads, isn't always an error at run time, sometimes the shader just reads garbage
+// `RenderPipelineBuilder::build` uses the interfaces from here to turn those mistakes into a `PipelineError`
+//
+// SPIR-V only needs a handful of instructions to be understood for this, so it's read by hand
+// WGSL is parsed with naga, the same way that `build.rs` validates it
+use std::collections::HashMap;
+
+// The type of a single component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarKind {
+    Float,
+    Double,
+    Sint,
+    Uint,
+}
+
+// The type of one location, a scalar or vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VaryingType {
+    pub kind: ScalarKind,
+    // 1 for scalars
+    pub components: u32,
+}
+
+impl VaryingType {
+    // What a shader sees when it reads a vertex attribute with `format`
+    // Normalized formats read as floats, even though the data in the buffer is integers
+    pub fn of_vertex_format(format: wgpu::VertexFormat) -> Self {
+        use wgpu::VertexFormat::*;
+        let (kind, components) = match format {
+            Uchar2Norm | Char2Norm | Ushort2Norm | Short2Norm | Half2 | Float2 => {
+                (ScalarKind::Float, 2)
+            }
+            Uchar4Norm | Char4Norm | Ushort4Norm | Short4Norm | Half4 | Float4 => {
+                (ScalarKind::Float, 4)
+            }
+            Float => (ScalarKind::Float, 1),
+            Float3 => (ScalarKind::Float, 3),
+            Uchar2 | Ushort2 | Uint2 => (ScalarKind::Uint, 2),
+            Uchar4 | Ushort4 | Uint4 => (ScalarKind::Uint, 4),
+            Uint => (ScalarKind::Uint, 1),
+            Uint3 => (ScalarKind::Uint, 3),
+            Char2 | Short2 | Int2 => (ScalarKind::Sint, 2),
+            Char4 | Short4 | Int4 => (ScalarKind::Sint, 4),
+            Int => (ScalarKind::Sint, 1),
+            Int3 => (ScalarKind::Sint, 3),
+            Double => (ScalarKind::Double, 1),
+            Double2 => (ScalarKind::Double, 2),
+            Double3 => (ScalarKind::Double, 3),
+            Double4 => (ScalarKind::Double, 4),
+        };
+        Self { kind, components }
+    }
+}
+
+// Written the way GLSL spells it, since that's what most of our shaders are in
+impl std::fmt::Display for VaryingType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (scalar, prefix) = match self.kind {
+            ScalarKind::Float => ("float", ""),
+            ScalarKind::Double => ("double", "d"),
+            ScalarKind::Sint => ("int", "i"),
+            ScalarKind::Uint => ("uint", "u"),
+        };
+        if self.components == 1 {
+            write!(f, "{}", scalar)
+        } else {
+            write!(f, "{}vec{}", prefix, self.components)
+        }
+    }
+}
+
+// A `layout(location=N) in` or `out`
+// Matrices and arrays take up one location per column or element, so they show up as several of these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Varying {
+    pub location: u32,
+    pub ty: VaryingType,
+}
+
+// Everything that one entry point reads and writes by location, sorted by location
+// Builtins like `gl_Position` and `gl_VertexIndex` aren't in here
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntryPointInterface {
+    pub inputs: Vec<Varying>,
+    pub outputs: Vec<Varying>,
+}
+
+impl EntryPointInterface {
+    pub fn input(&self, location: u32) -> Option<VaryingType> {
+        find(&self.inputs, location)
+    }
+
+    pub fn output(&self, location: u32) -> Option<VaryingType> {
+        find(&self.outputs, location)
+    }
+}
+
+fn find(varyings: &[Varying], location: u32) -> Option<VaryingType> {
+    varyings
+        .iter()
+        .find(|varying| varying.location == location)
+        .map(|varying| varying.ty)
+}
+
+// The interface of every entry point in a module, by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShaderInterface {
+    entry_points: HashMap<String, EntryPointInterface>,
+}
+
+impl ShaderInterface {
+    // Returns an error message if the shader couldn't be understood, wgpu will most likely reject it as well then
+    pub fn reflect(source: &wgpu::ShaderSource) -> Result<Self, String> {
+        match source {
+            wgpu::ShaderSource::SpirV(words) => reflect_spirv(words),
+            wgpu::ShaderSource::Wgsl(source) => reflect_wgsl(source),
+        }
+    }
+
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPointInterface> {
+        self.entry_points.get(name)
+    }
+}
+
+// Every location from `location` on that a variable of type `ty` takes up
+fn expand(location: u32, ty: &[VaryingType]) -> impl Iterator<Item = Varying> + '_ {
+    ty.iter().enumerate().map(move |(i, ty)| Varying {
+        location: location + i as u32,
+        ty: *ty,
+    })
+}
+
+// https://www.khronos.org/registry/spir-v/specs/unified1/SPIRV.html for what all of these numbers mean
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const SPIRV_HEADER_WORDS: usize = 5;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+const DECORATION_LOCATION: u32 = 30;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_OUTPUT: u32 = 3;
+
+fn reflect_spirv(words: &[u32]) -> Result<ShaderInterface, String> {
+    if words.len() < SPIRV_HEADER_WORDS || words[0] != SPIRV_MAGIC {
+        return Err("not SPIR-V, the magic number is wrong".to_string());
+    }
+
+    // Each of these is keyed by the id of what it describes
+    let mut entry_points = Vec::new();
+    let mut locations = HashMap::new();
+    // What each type id is, as the list of locations that it takes up
+    let mut types: HashMap<u32, Vec<VaryingType>> = HashMap::new();
+    let mut constants = HashMap::new();
+    // Pointer id => (storage class, pointee type id)
+    let mut pointers = HashMap::new();
+    // Variable id => pointer type id
+    let mut variables = HashMap::new();
+
+    let mut rest = &words[SPIRV_HEADER_WORDS..];
+    while !rest.is_empty() {
+        // The first word of every instruction is its length in words, then its opcode
+        let length = (rest[0] >> 16) as usize;
+        let opcode = rest[0] & 0xffff;
+        if length == 0 || length > rest.len() {
+            return Err("SPIR-V instruction runs past the end of the module".to_string());
+        }
+        let operands = &rest[1..length];
+        rest = &rest[length..];
+
+        let operand = |i: usize| {
+            operands
+                .get(i)
+                .copied()
+                .ok_or_else(|| format!("SPIR-V instruction {} is missing operands", opcode))
+        };
+        match opcode {
+            OP_ENTRY_POINT => {
+                // Execution model, function id, name, then the ids of every variable that it uses
+                let (name, name_words) = read_string(operands.get(2..).unwrap_or_default());
+                let interface = operands.get(2 + name_words..).unwrap_or_default();
+                entry_points.push((name, interface.to_vec()));
+            }
+            OP_DECORATE if operand(1)? == DECORATION_LOCATION => {
+                locations.insert(operand(0)?, operand(2)?);
+            }
+            OP_TYPE_INT => {
+                let kind = if operand(2)? == 1 {
+                    ScalarKind::Sint
+                } else {
+                    ScalarKind::Uint
+                };
+                types.insert(
+                    operand(0)?,
+                    vec![VaryingType {
+                        kind,
+                        components: 1,
+                    }],
+                );
+            }
+            OP_TYPE_FLOAT => {
+                let kind = if operand(1)? == 64 {
+                    ScalarKind::Double
+                } else {
+                    ScalarKind::Float
+                };
+                types.insert(
+                    operand(0)?,
+                    vec![VaryingType {
+                        kind,
+                        components: 1,
+                    }],
+                );
+            }
+            OP_TYPE_VECTOR => {
+                if let Some([scalar]) = types.get(&operand(1)?).map(Vec::as_slice) {
+                    let vector = VaryingType {
+                        components: operand(2)?,
+                        ..*scalar
+                    };
+                    types.insert(operand(0)?, vec![vector]);
+                }
+            }
+            // A matrix takes up one location per column
+            OP_TYPE_MATRIX => {
+                if let Some([column]) = types.get(&operand(1)?).map(Vec::as_slice) {
+                    let columns = vec![*column; operand(2)? as usize];
+                    types.insert(operand(0)?, columns);
+                }
+            }
+            // And an array one location (or more, for arrays of matrices) per element
+            OP_TYPE_ARRAY => {
+                if let (Some(element), Some(length)) =
+                    (types.get(&operand(1)?), constants.get(&operand(2)?))
+                {
+                    let elements = element.repeat(*length as usize);
+                    types.insert(operand(0)?, elements);
+                }
+            }
+            OP_CONSTANT => {
+                constants.insert(operand(1)?, operand(2)?);
+            }
+            OP_TYPE_POINTER => {
+                pointers.insert(operand(0)?, (operand(1)?, operand(2)?));
+            }
+            OP_VARIABLE => {
+                variables.insert(operand(1)?, operand(0)?);
+            }
+            _ => {}
+        }
+    }
+
+    let mut interface = ShaderInterface::default();
+    for (name, ids) in entry_points {
+        let mut entry_point = EntryPointInterface::default();
+        for id in ids {
+            // Builtins don't have a location, and neither do structs like `gl_PerVertex`, so they're skipped
+            let location = match locations.get(&id) {
+                Some(location) => *location,
+                None => continue,
+            };
+            let (storage_class, ty) = match variables.get(&id).and_then(|ty| pointers.get(ty)) {
+                Some(pointer) => *pointer,
+                None => continue,
+            };
+            // Types we don't understand (e.g. structs) aren't checked at all, rather than being reported as wrong
+            let ty = match types.get(&ty) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            let varyings = match storage_class {
+                STORAGE_CLASS_INPUT => &mut entry_point.inputs,
+                STORAGE_CLASS_OUTPUT => &mut entry_point.outputs,
+                _ => continue,
+            };
+            varyings.extend(expand(location, ty));
+        }
+        entry_point.inputs.sort_by_key(|varying| varying.location);
+        entry_point.outputs.sort_by_key(|varying| varying.location);
+        interface.entry_points.insert(name, entry_point);
+    }
+    Ok(interface)
+}
+
+// SPIR-V strings are UTF-8, null terminated and padded out to a whole number of words
+// Returns the string and how many words it took up
+fn read_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes().iter() {
+            if *byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(*byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+fn reflect_wgsl(source: &str) -> Result<ShaderInterface, String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| e.to_string())?;
+
+    let mut interface = ShaderInterface::default();
+    for ((_, name), entry_point) in &module.entry_points {
+        let mut interface_of_entry_point = EntryPointInterface::default();
+        for (handle, global) in module.global_variables.iter() {
+            // Inputs and outputs are globals in naga, only the ones that the entry point touches belong to it
+            let used = matches!(
+                entry_point.function.global_usage.get(handle.index()),
+                Some(usage) if !usage.is_empty()
+            );
+            let location = match global.binding {
+                Some(naga::Binding::Location(location)) if used => location,
+                _ => continue,
+            };
+            let varyings = match global.class {
+                naga::StorageClass::Input => &mut interface_of_entry_point.inputs,
+                naga::StorageClass::Output => &mut interface_of_entry_point.outputs,
+                _ => continue,
+            };
+            if let Some(ty) = wgsl_type(&module.types[global.ty].inner) {
+                varyings.extend(expand(location, &ty));
+            }
+        }
+        interface_of_entry_point
+            .inputs
+            .sort_by_key(|varying| varying.location);
+        interface_of_entry_point
+            .outputs
+            .sort_by_key(|varying| varying.location);
+        interface
+            .entry_points
+            .insert(name.clone(), interface_of_entry_point);
+    }
+    Ok(interface)
+}
+
+// The locations that a WGSL type takes up, `None` for types that can't be inputs or outputs anyway
+fn wgsl_type(ty: &naga::TypeInner) -> Option<Vec<VaryingType>> {
+    let kind = |kind, width| match (kind, width) {
+        (naga::ScalarKind::Float, 8) => Some(ScalarKind::Double),
+        (naga::ScalarKind::Float, _) => Some(ScalarKind::Float),
+        (naga::ScalarKind::Sint, _) => Some(ScalarKind::Sint),
+        (naga::ScalarKind::Uint, _) => Some(ScalarKind::Uint),
+        (naga::ScalarKind::Bool, _) => None,
+    };
+    match *ty {
+        naga::TypeInner::Scalar {
+            kind: scalar,
+            width,
+        } => Some(vec![VaryingType {
+            kind: kind(scalar, width)?,
+            components: 1,
+        }]),
+        naga::TypeInner::Vector {
+            size,
+            kind: scalar,
+            width,
+        } => Some(vec![VaryingType {
+            kind: kind(scalar, width)?,
+            components: size as u32,
+        }]),
+        naga::TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => Some(vec![
+            VaryingType {
+                kind: kind(naga::ScalarKind::Float, width)?,
+                components: rows as u32,
+            };
+            columns as usize
+        ]),
+        _ => None,
+    }
+}
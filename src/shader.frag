@@ -9,10 +9,36 @@
 // `inout` passed into a function means that the value of the param is being read and set
 // `layout` specifies where `f_color` will be saved to 
 // `location=0` is current texture of the swapchain, which is the screen
+#ifdef TEXTURED
+// From `shader.vert[TEXTURED]`, where in the texture this pixel is
+layout(location=0) in vec2 v_tex_coords;
+// The texture that `State` binds to group 1 (see `src/texture.rs`), combined into a `sampler2D` when it's sampled
+layout(set=1, binding=0) uniform texture2D t_diffuse;
+layout(set=1, binding=1) uniform sampler s_diffuse;
+#else
 layout(location=0) in vec3 v_color;
+#endif
 layout(location=0) out vec4 f_color;
 
+// `u_resolution`, `u_cursor`, `u_time` and friends, shared with every other shader (see `src/shaders/include/`)
+#include <globals.glsl>
+
 void main() {
-  f_color = vec4(v_color, 1.0);
+#ifdef TEXTURED
+  // The `shader.frag[TEXTURED]` variant draws the texture as it is, so sprites keep their colors
+  f_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords);
+#else
+  // Slowly pulse the brightness, starting at full brightness
+  float pulse = 0.75 + 0.25 * cos(u_time);
+  // Light up the part of the triangle that's under the cursor
+  float glow = 0.25 * (1.0 - smoothstep(0.0, 50.0, distance(gl_FragCoord.xy, u_cursor)));
+#ifdef FLAT_COLOR
+  // The `shader.frag[FLAT_COLOR]` variant (see `src/shaders/manifest.toml`) ignores the vertex colors
+  vec3 color = vec3(1.0, 0.5, 0.0);
+#else
+  vec3 color = v_color;
+#endif
+  f_color = vec4(color * pulse + glow, 1.0);
   // f_color = vec4(0.1, 0.2, 0.3, 1.0);
+#endif
 }
\ No newline at end of file
@@ -0,0 +1,412 @@
+// Keeps every compiled shader module around by name, so pipelines can refer to shaders like `"shader.vert"`
+use crate::includes;
+use crate::reflect::ShaderInterface;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// `build.rs` compiles every shader in `src/` into `OUT_DIR` and writes a module that embeds them
+// It defines the `BuiltinShader` enum, with a variant for each shader, e.g. `src/shader.vert` => `BuiltinShader::ShaderVert`
+mod builtin {
+    use super::{ShaderEntryPoint, ShaderLanguage, ShaderStage, ShaderVariant, GLSL_ENTRY_POINT};
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+pub use builtin::{BuiltinShader, ENTRY_POINTS, VARIANTS};
+
+// Which part of the pipeline a shader runs in
+// GLSL files have one stage, from their extension, while a WGSL file can have an entry point for every stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+    // `.geom`, `.tesc` and `.tese`
+    Geometry,
+    TessControl,
+    TessEvaluation,
+}
+
+impl ShaderStage {
+    // wgpu only has vertex, fragment and compute shaders, the others get compiled and checked but can't be loaded yet
+    pub const fn is_supported(self) -> bool {
+        matches!(
+            self,
+            ShaderStage::Vertex | ShaderStage::Fragment | ShaderStage::Compute
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderLanguage {
+    // `.vert`, `.frag`, `.comp`, `.geom`, `.tesc` and `.tese`, compiled to SPIR-V by shaderc
+    Glsl,
+    // `.wgsl`, validated by naga in `build.rs` and handed to wgpu as it is
+    Wgsl,
+}
+
+// A copy of a builtin shader compiled with extra `#define`s, declared in `src/shaders/manifest.toml`
+#[derive(Debug)]
+pub struct ShaderVariant {
+    pub shader: BuiltinShader,
+    // e.g. `shader.frag[FLAT_COLOR]`, the name in the `ShaderLibrary`
+    pub name: &'static str,
+    // Sorted by name, a define without a value has `""` as its value
+    pub defines: &'static [(&'static str, &'static str)],
+    pub spirv: &'static [u8],
+}
+
+// What the entry point of a module compiled from GLSL is called
+// glslang only compiles `main`, other functions get `#define`d to `main` first and their name is gone from the SPIR-V
+pub const GLSL_ENTRY_POINT: &str = "main";
+
+// One entry point of a GLSL shader that has several, declared in `src/shaders/manifest.toml`
+// Every one of them is compiled into its own module, since a SPIR-V module from shaderc only has the one
+#[derive(Debug)]
+pub struct ShaderEntryPoint {
+    pub shader: BuiltinShader,
+    pub stage: ShaderStage,
+    // The name of the function, what goes in `vertex_entry_point` / `fragment_entry_point`
+    pub name: &'static str,
+    // e.g. `post/blur.frag:blur_y`, the name of the module in the `ShaderLibrary` (see `entry_point_module_name`)
+    pub module: &'static str,
+    // What the entry point is called in `spirv`, `GLSL_ENTRY_POINT` like every GLSL module
+    pub module_entry_point: &'static str,
+    pub spirv: &'static [u8],
+}
+
+// The name in the `ShaderLibrary` of the module that has just `entry_point` out of the shader called `name`
+// Only GLSL shaders with several entry points have these, `ShaderLibrary::entry_point` falls back to `name` otherwise
+pub fn entry_point_module_name(name: &str, entry_point: &str) -> String {
+    format!("{}:{}", name, entry_point)
+}
+
+impl BuiltinShader {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|shader| shader.name() == name)
+    }
+
+    // Every builtin shader with an entry point for `stage`, e.g. all of the fragment shaders
+    pub fn with_stage(stage: ShaderStage) -> impl Iterator<Item = Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(move |shader| shader.stages().contains(&stage))
+    }
+
+    // The separate modules for the entry points, empty unless the shader has more than one
+    pub fn entry_point_modules(self) -> impl Iterator<Item = &'static ShaderEntryPoint> {
+        ENTRY_POINTS
+            .iter()
+            .filter(move |entry_point| entry_point.shader == self)
+    }
+
+    // Whether wgpu can load the shader at all, see `ShaderStage::is_supported`
+    pub fn is_supported(self) -> bool {
+        self.stages().iter().all(|stage| stage.is_supported())
+    }
+
+    pub fn variants(self) -> impl Iterator<Item = &'static ShaderVariant> {
+        VARIANTS
+            .iter()
+            .filter(move |variant| variant.shader == self)
+    }
+
+    // Finds the variant that was compiled with exactly `defines`, in any order
+    // Use `""` as the value of defines that don't have one
+    pub fn variant(self, defines: &[(&str, &str)]) -> Option<&'static ShaderVariant> {
+        let mut defines = defines.to_vec();
+        defines.sort_unstable();
+        self.variants()
+            .find(|variant| variant.defines == defines.as_slice())
+    }
+
+    // Where the source of the shader lives, only makes sense on the machine that the program was built on
+    pub fn source_path(self) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src")
+            .join(self.name())
+    }
+
+    // Every file that the shader `#include`d when it was built, which hot reload watches along with `source_path`
+    pub fn include_paths(self) -> Vec<PathBuf> {
+        self.includes()
+            .iter()
+            .map(|include| Path::new(env!("CARGO_MANIFEST_DIR")).join(include))
+            .collect()
+    }
+}
+
+// Everything that can go wrong when compiling shaders while the program is running
+#[derive(Debug)]
+pub enum ShaderError {
+    // Couldn't read the source file
+    Io(std::io::Error),
+    // shaderc couldn't set itself up
+    NoCompiler,
+    // The GLSL didn't compile, holds shaderc's error messages
+    Compile(String),
+    // The file extension isn't one that `compile_file` knows, so we don't know what kind of shader it is
+    UnknownKind(PathBuf),
+    // WGSL doesn't have a preprocessor, so it can't have variants
+    WgslDefines(PathBuf),
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Io(e) => write!(f, "failed to read shader: {}", e),
+            ShaderError::NoCompiler => write!(f, "unable to create shader compiler"),
+            ShaderError::Compile(message) => write!(f, "failed to compile shader:\n{}", message),
+            ShaderError::UnknownKind(path) => {
+                write!(f, "don't know what kind of shader {} is", path.display())
+            }
+            ShaderError::WgslDefines(path) => write!(
+                f,
+                "{} is WGSL, which doesn't support `#define`s",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<std::io::Error> for ShaderError {
+    fn from(e: std::io::Error) -> Self {
+        ShaderError::Io(e)
+    }
+}
+
+// Same thing that `build.rs` does for the shaders in `src/`, but at run time
+// `name` shows up in the error messages, and quoted `#include`s are looked up next to it
+// `defines` are `(name, value)` pairs, like a `ShaderVariant`'s
+// `entry_point` is the function to compile, it's renamed to `main` so it doesn't have to be called that in the source
+// The module always calls it `GLSL_ENTRY_POINT`
+pub fn compile_glsl(
+    source: &str,
+    kind: shaderc::ShaderKind,
+    name: &str,
+    entry_point: &str,
+    defines: &[(&str, &str)],
+) -> Result<CompiledShader, ShaderError> {
+    let mut compiler = shaderc::Compiler::new().ok_or(ShaderError::NoCompiler)?;
+    let included = RefCell::new(Vec::new());
+    let mut options = shaderc::CompileOptions::new().ok_or(ShaderError::NoCompiler)?;
+    // Same include directories as `build.rs`, anything built in is used when they aren't there
+    includes::set_include_callback(
+        &mut options,
+        includes::include_dirs(Path::new(env!("CARGO_MANIFEST_DIR"))),
+        |path| included.borrow_mut().push(path.to_path_buf()),
+    );
+    for (define, value) in defines {
+        options.add_macro_definition(define, Some(value));
+    }
+    if entry_point != GLSL_ENTRY_POINT {
+        options.add_macro_definition(entry_point, Some(GLSL_ENTRY_POINT));
+    }
+    let artifact = compiler
+        .compile_into_spirv(source, kind, name, GLSL_ENTRY_POINT, Some(&options))
+        .map_err(|e| ShaderError::Compile(e.to_string()))?;
+    // The callback borrows `included`
+    drop(options);
+    Ok(CompiledShader::SpirV {
+        spirv: artifact.as_binary().to_vec(),
+        includes: included.into_inner(),
+    })
+}
+
+// A shader that's ready to be turned into a module
+pub enum CompiledShader {
+    // `includes` are the files that got `#include`d, so that hot reload can watch them too
+    SpirV {
+        spirv: Vec<u32>,
+        includes: Vec<PathBuf>,
+    },
+    // wgpu compiles WGSL itself
+    Wgsl(String),
+}
+
+impl CompiledShader {
+    pub fn source(&self) -> wgpu::ShaderSource<'_> {
+        match self {
+            CompiledShader::SpirV { spirv, .. } => wgpu::ShaderSource::SpirV(spirv.into()),
+            CompiledShader::Wgsl(source) => wgpu::ShaderSource::Wgsl(source.into()),
+        }
+    }
+
+    pub fn includes(&self) -> &[PathBuf] {
+        match self {
+            CompiledShader::SpirV { includes, .. } => includes,
+            CompiledShader::Wgsl(_) => &[],
+        }
+    }
+}
+
+// Compiles a GLSL or `.wgsl` file, the kind of shader comes from the extension like in `build.rs`
+// WGSL files keep all of their entry points, so `entry_point` only matters for GLSL
+pub fn compile_file(
+    path: &Path,
+    entry_point: &str,
+    defines: &[(&str, &str)],
+) -> Result<CompiledShader, ShaderError> {
+    let kind = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("vert") => shaderc::ShaderKind::Vertex,
+        Some("frag") => shaderc::ShaderKind::Fragment,
+        Some("comp") => shaderc::ShaderKind::Compute,
+        Some("geom") => shaderc::ShaderKind::Geometry,
+        Some("tesc") => shaderc::ShaderKind::TessControl,
+        Some("tese") => shaderc::ShaderKind::TessEvaluation,
+        Some("wgsl") if defines.is_empty() => {
+            return Ok(CompiledShader::Wgsl(std::fs::read_to_string(path)?))
+        }
+        Some("wgsl") => return Err(ShaderError::WgslDefines(path.to_path_buf())),
+        _ => return Err(ShaderError::UnknownKind(path.to_path_buf())),
+    };
+    let source = std::fs::read_to_string(path)?;
+    compile_glsl(
+        &source,
+        kind,
+        &path.display().to_string(),
+        entry_point,
+        defines,
+    )
+}
+
+// A module in the `ShaderLibrary`, along with what it reads and writes
+pub struct LoadedShader {
+    pub module: wgpu::ShaderModule,
+    // `None` if reflecting the shader failed, then pipelines using it just aren't checked
+    pub interface: Option<ShaderInterface>,
+}
+
+impl LoadedShader {
+    pub fn new(device: &wgpu::Device, name: &str, source: wgpu::ShaderSource) -> Self {
+        // Reflected first, since creating the module takes the source
+        let interface = ShaderInterface::reflect(&source).ok();
+        Self {
+            module: create_module(device, name, source),
+            interface,
+        }
+    }
+}
+
+pub struct ShaderLibrary {
+    shaders: HashMap<String, LoadedShader>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self {
+            shaders: HashMap::new(),
+        }
+    }
+
+    // Loads every shader that `build.rs` compiled, with all of their variants and entry points
+    // Except for the stages that wgpu doesn't have
+    pub fn load_builtin(device: &wgpu::Device) -> Self {
+        let mut library = Self::new();
+        // `make_spirv` checks the magic number and copies the bytes into properly aligned `u32`s
+        let variants = VARIANTS
+            .iter()
+            .filter(|variant| variant.shader.is_supported())
+            .map(|variant| (variant.name, wgpu::util::make_spirv(variant.spirv)));
+        let entry_points = ENTRY_POINTS
+            .iter()
+            .filter(|entry_point| entry_point.stage.is_supported())
+            .map(|entry_point| {
+                (
+                    entry_point.module,
+                    wgpu::util::make_spirv(entry_point.spirv),
+                )
+            });
+        let shaders = BuiltinShader::ALL
+            .iter()
+            .filter(|shader| shader.is_supported())
+            .map(|shader| (shader.name(), shader.source()))
+            .chain(variants)
+            .chain(entry_points);
+        for (name, source) in shaders {
+            library.insert(name, LoadedShader::new(device, name, source));
+        }
+        library
+    }
+
+    // Adds a shader, replacing and returning any shader that already had the same name
+    pub fn insert(&mut self, name: &str, shader: LoadedShader) -> Option<LoadedShader> {
+        self.shaders.insert(name.to_string(), shader)
+    }
+
+    // Turns an already compiled shader into a module and adds it
+    pub fn insert_compiled(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        shader: &CompiledShader,
+    ) -> Option<LoadedShader> {
+        self.insert(name, LoadedShader::new(device, name, shader.source()))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<LoadedShader> {
+        self.shaders.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&wgpu::ShaderModule> {
+        self.shaders.get(name).map(|shader| &shader.module)
+    }
+
+    // The module (and interface) that has `entry_point` in it, out of the shader called `name`,
+    // and what the entry point is called inside of that module
+    // That's the entry point's own module for GLSL shaders with several of them, where it's `GLSL_ENTRY_POINT`,
+    // otherwise the shader's only module, where it has the name it was asked for
+    pub fn entry_point<'e>(
+        &self,
+        name: &str,
+        entry_point: &'e str,
+    ) -> Option<(&LoadedShader, &'e str)> {
+        match self
+            .shaders
+            .get(&entry_point_module_name(name, entry_point))
+        {
+            Some(shader) => Some((shader, GLSL_ENTRY_POINT)),
+            None => self.shaders.get(name).map(|shader| (shader, entry_point)),
+        }
+    }
+
+    pub fn interface(&self, name: &str) -> Option<&ShaderInterface> {
+        self.shaders
+            .get(name)
+            .and_then(|shader| shader.interface.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.shaders.keys().map(String::as_str)
+    }
+}
+
+pub fn create_module(
+    device: &wgpu::Device,
+    name: &str,
+    source: wgpu::ShaderSource,
+) -> wgpu::ShaderModule {
+    // wgpu validates modules with naga, and naga 0.3 can't validate SPIR-V that calls a function returning `void`
+    // That's how the playground calls `mainImage`, and how a lot of GLSL with helper functions looks, so SPIR-V skips it
+    // Pipelines are still checked against what `reflect` finds in them, WGSL keeps it since naga has to parse it anyway
+    let flags = match source {
+        wgpu::ShaderSource::SpirV(_) => wgpu::ShaderFlags::empty(),
+        wgpu::ShaderSource::Wgsl(_) => wgpu::ShaderFlags::VALIDATION,
+    };
+    device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some(name),
+        source,
+        flags,
+    })
+}
+
+impl Default for ShaderLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -24,14 +24,32 @@
 //   vec2(0.5, -0.5)
 // );
 
+// `u_view_proj` for the `shader.vert[CAMERA]` variant
+#include <globals.glsl>
+
 // Get position data from vertex buffer
 // Position
 layout(location=0) in vec3 a_position;
+#ifdef TEXTURED
+// The `shader.vert[TEXTURED]` variant reads a `TexturedVertex` instead, and passes its texture coordinates on
+layout(location=1) in vec2 a_tex_coords;
+layout(location=0) out vec2 v_tex_coords;
+#else
 // Color
 layout(location=1) in vec3 a_color;
+#ifdef INSTANCED
+// The `shader.vert[INSTANCED]` variant also gets an `Instance` (see `src/instance.rs`) for every copy of the mesh
+// One column of the model matrix per location, see `src/instance.rs` for why it isn't an `in mat4`
+layout(location=2) in vec4 a_model_0;
+layout(location=3) in vec4 a_model_1;
+layout(location=4) in vec4 a_model_2;
+layout(location=5) in vec4 a_model_3;
+layout(location=6) in vec4 a_instance_color;
+#endif
 
 // Literally just passthrough color to the shader.frag
 layout(location=0) out vec3 v_color;
+#endif
 
 
 void main () {
@@ -52,12 +70,27 @@ void main () {
   // You can even call it like `array.rgb`
 
   // Return color for frag shader to deal with
+#ifdef TEXTURED
+  v_tex_coords = a_tex_coords;
+#elif defined(INSTANCED)
+  v_color = a_color * a_instance_color.rgb;
+#else
   v_color = a_color;
+#endif
   
   // The way that the data types are setup makes it easy to do matrix operations on them 
   // gl_Position = vec4(a_position, 1.0);
   // if(gl_VertexIndex == 2) {
+#ifdef CAMERA
+  // The `shader.vert[CAMERA]` variant treats the positions as being in the world, and looks at them through `State::camera`
+  gl_Position = u_view_proj * vec4(a_position, 1.0);
+#elif defined(INSTANCED)
+  // Each copy gets moved into the world by its own model matrix first
+  mat4 model = mat4(a_model_0, a_model_1, a_model_2, a_model_3);
+  gl_Position = u_view_proj * model * vec4(a_position, 1.0);
+#else
   gl_Position = vec4(a_position, 1.0);
+#endif
   // } else {
     // gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
   // }
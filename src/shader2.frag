@@ -12,6 +12,12 @@
 layout(location=0) out vec4 f_color;
 layout(location=1) in vec4 triangle;
 
+// `u_resolution`, `u_cursor`, `u_time` and friends, shared with every other shader (see `src/shaders/include/`)
+#include <globals.glsl>
+
 void main() {
-  f_color = vec4(triangle.r, triangle.g, triangle.b, triangle.a);
+  // Fade in a color from the position of the pixel on the screen, the longer the app runs
+  vec2 uv = gl_FragCoord.xy / u_resolution;
+  float fade = 0.5 - 0.5 * cos(u_time);
+  f_color = vec4(mix(triangle.rgb, vec3(uv, 1.0 - uv.x), fade), triangle.a);
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+// `globals.glsl`
+// Data that changes every frame, uploaded from `State::render` (see `src/uniforms.rs`)
+// Pull it into a shader with `#include <globals.glsl>`
+
+layout(set=0, binding=0) uniform Globals {
+  // Size of the frame in pixels
+  vec2 u_resolution;
+  // Cursor position in pixels, same coordinates as `gl_FragCoord`
+  vec2 u_cursor;
+  // Shadertoy's `iMouse`, see `src/playground.rs`
+  vec4 u_mouse;
+  // Seconds since the app started
+  float u_time;
+  uint u_frame;
+  // The camera's view-projection matrix, see `src/camera.rs`
+  mat4 u_view_proj;
+};
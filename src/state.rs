@@ -0,0 +1,1093 @@
+use crate::camera::{Camera, CameraController, CameraMode};
+use crate::capture;
+use crate::hot_reload::{ErrorCatcher, Reload, ShaderWatcher};
+use crate::instance::{Instance, InstancedMesh};
+use crate::pipeline::{PipelineError, PipelineRegistry, RenderPipelineBuilder, VertexSource};
+use crate::playground;
+use crate::record::{Recorder, RecordingOutput};
+use crate::shader::{
+    self, BuiltinShader, CompiledShader, LoadedShader, ShaderError, ShaderLibrary, ShaderStage,
+};
+use crate::texture::{self, DepthTexture, MultisampleTexture, Texture, TextureError};
+use crate::uniforms::{Globals, GlobalsBinding};
+use crate::vertex::{TexturedVertex, QUAD_INDICES, QUAD_VERTICES};
+use crate::{Batch, Mesh, Vertex, VertexLayout, VERTICES};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use winit::{event::*, window::Window};
+
+// Where `State::render` draws its frames to
+enum RenderTarget {
+    // The normal case, drawing to the screen through a swap chain
+    Window {
+        // Platform specific "surface" in which rendered images can be put
+        surface: wgpu::Surface,
+        swap_chain: wgpu::SwapChain,
+    },
+    // Drawing into a texture that only exists on the GPU, for when there's no display (build servers, tests)
+    Offscreen {
+        texture: wgpu::Texture,
+    },
+}
+
+// The texture that a single call to `render` is drawing to
+enum Frame {
+    SwapChain(wgpu::SwapChainTexture),
+    Offscreen(wgpu::TextureView),
+}
+
+impl Frame {
+    fn view(&self) -> &wgpu::TextureView {
+        match self {
+            Frame::SwapChain(texture) => &texture.view,
+            Frame::Offscreen(view) => view,
+        }
+    }
+}
+
+pub struct State {
+    target: RenderTarget,
+    // Open connection to the GPU, responsible for creating rendering and compute processes in the form of commands, which are submitted to the queue
+    pub device: wgpu::Device,
+    // Executes the command buffer, provides methods for writing to buffers and textures
+    pub queue: wgpu::Queue,
+    // Also used to describe the offscreen texture in headless mode, since it needs the same format, width and height
+    pub sc_desc: wgpu::SwapChainDescriptor,
+    pub size: winit::dpi::PhysicalSize<u32>,
+    // Every pipeline that `Batch::active_pipeline` can pick from
+    pipelines: PipelineRegistry,
+    // Kept around so that pipelines can still be added and rebuilt after `new`, like the playground
+    pipeline_layout: wgpu::PipelineLayout,
+    shaders: ShaderLibrary,
+    // What pipelines with `VertexSource::VertexBuffer` draw
+    mesh: Mesh,
+    // What pipelines with `VertexSource::TexturedQuad` draw
+    quad: Mesh,
+    // What pipelines with `VertexSource::Instanced` draw, one draw call each
+    instanced_meshes: Vec<InstancedMesh>,
+    // Bound at group 1 for every pipeline, a checkerboard until `set_texture` is called
+    texture: Texture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group: wgpu::BindGroup,
+    // Only made once a pipeline with a depth test is added, then it's recreated along with the swap chain
+    depth: Option<DepthTexture>,
+    // How many samples per pixel everything is drawn with, 1 (no MSAA) until `set_sample_count` is called
+    sample_count: u32,
+    // What gets drawn into instead of the frame while `sample_count` is more than 1
+    multisample: Option<MultisampleTexture>,
+    // Catches wgpu errors around the calls that are allowed to fail, like reloading a shader or trying a sample count
+    errors: ErrorCatcher,
+    // Where to save the next frame that gets rendered, if a screenshot was requested
+    screenshot_path: Option<PathBuf>,
+    // Captures every frame while we're recording
+    recorder: Option<Recorder>,
+    // Simulated time, advanced by `update`
+    elapsed: Duration,
+    // Uploaded to the shaders every frame, at `layout(set=0, binding=0)`
+    globals: GlobalsBinding,
+    // What `u_view_proj` in the globals is worked out from
+    camera: Camera,
+    // Moves the camera around with the mouse and keyboard events that `input` gets
+    camera_controller: CameraController,
+    // How many frames have been rendered so far
+    frame: u32,
+    // Where the playground shader was loaded from, if there is one
+    playground_path: Option<PathBuf>,
+    // Watches the shader sources, only while hot reloading is enabled
+    hot_reload: Option<HotReload>,
+}
+
+struct HotReload {
+    watcher: ShaderWatcher,
+}
+
+impl State {
+    pub async fn new(window: &Window) -> Self {
+        let size = window.inner_size();
+
+        // The first thing to create for wgpu is an instance
+        // BackendBit::Primary => the backend with primary tier of support, like vulkan, dx12, etc.
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        // Primary use of an instance is to create `Surfaces` and `Adapters`
+        // This creates a surface from a "raw window handle", provided by winit
+        let surface = unsafe { instance.create_surface(window) };
+        // The adapter is a handle to the actual GPU
+        // We request it with certain "hard" (mandatory) and "soft" (priority) query options
+        let adapter = instance
+            .request_adapter(
+                // This is creating a type (struct is a category of type) from the wgpu library
+                &wgpu::RequestAdapterOptions {
+                    // Power preference default
+                    // ff//df
+                    power_preference: wgpu::PowerPreference::default(),
+                    // Make sure that the GPU can actually display stuff on the surface that we made using the wgpu instance ealier
+                    compatible_surface: Some(&surface),
+                },
+            )
+            .await
+            .unwrap();
+        // The adapter that we made earlier is now being used to create the `device` and `queue`
+        // `adapter.request_device()` opens an actual connection to the GPU, returning the `queue` and `device`
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    // `Features` field on `DeviceDescriptor` describes the features that we want
+                    // The features themselves can be device-specific and thus not cross-platform, care
+                    features: wgpu::Features::empty(),
+                    // Limitations of a certain device or adapter
+                    limits: wgpu::Limits::default(),
+                    // "Debug label for device"
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        // A swap chain descriptor describes the characteristics of a swap chain
+        let sc_desc = wgpu::SwapChainDescriptor {
+            // How will the swap chain be used? (Only option is RENDER_ATTACHMENT), which outputs texture to the screen
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            // How the textures are formatted in the swap chain, different displays have different formats,
+            // we can call an adaptor func to get the right texture format
+            format: adapter.get_swap_chain_preferred_format(&surface),
+            // Width and height of the swap chain, which are the width and height of the surface, (in this case a `window`)
+            width: size.width,
+            height: size.height,
+            // The mode that the swap chain will be presented in
+            // Uses the enum `PresentMode`, which has options of `FIFO` (vsync), `Immediate` (vsync-off) and `Mailbox` (hybrid)
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        // Represents the image or series of images that will be drawn onto a `Surface`
+        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
+
+        Self::from_parts(
+            device,
+            queue,
+            sc_desc,
+            size,
+            RenderTarget::Window {
+                surface,
+                swap_chain,
+            },
+        )
+    }
+
+    // Same as `new`, except that no window is needed, we render into an offscreen texture of `width` x `height` instead
+    // Returns `None` if there's no adapter that we can use (e.g. no GPU and no software renderer installed)
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Option<Self> {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        // No surface this time, so we don't care whether the adapter can present to one
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+            })
+            .await?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        // There's no swap chain, but the descriptor is still the easiest way to keep track of the format and size
+        let sc_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let texture = Self::create_offscreen_texture(&device, &sc_desc);
+
+        Some(Self::from_parts(
+            device,
+            queue,
+            sc_desc,
+            size,
+            RenderTarget::Offscreen { texture },
+        ))
+    }
+
+    // Offscreen textures have to be `COPY_SRC` as well, so that we can copy the pixels out of them afterwards
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        sc_desc: &wgpu::SwapChainDescriptor,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: sc_desc.format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        })
+    }
+
+    // Everything after getting a device is the same for both the windowed and the headless state
+    fn from_parts(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        sc_desc: wgpu::SwapChainDescriptor,
+        size: winit::dpi::PhysicalSize<u32>,
+        target: RenderTarget,
+    ) -> Self {
+        let globals = GlobalsBinding::new(&device);
+        let texture = Texture::checkerboard(&device, &queue, 64, 8);
+        let texture_bind_group_layout = texture::create_bind_group_layout(&device);
+        let texture_bind_group = texture.create_bind_group(&device, &texture_bind_group_layout);
+
+        // Pipeline layout describes a pipeline
+        let pipeline_layout =
+            // `PipelineLayoutDescriptor` can be used to create a pipeline layout
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                // Every pipeline gets the globals at group 0 and the texture at group 1, whether it uses them or not
+                bind_group_layouts: &[&globals.bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let shaders = ShaderLibrary::load_builtin(&device);
+        let mesh = Mesh::new(&device, "Triangle", VERTICES);
+        let quad = Mesh::indexed(&device, "Quad", QUAD_VERTICES, QUAD_INDICES)
+            .expect("QUAD_INDICES points past the end of QUAD_VERTICES");
+        let format = sc_desc.format;
+        let errors = ErrorCatcher::install(&device);
+        let camera = Camera::new(sc_desc.width, sc_desc.height);
+
+        // We can return the struct that can be built using all of our variables
+        let mut state = Self {
+            target,
+            device,
+            queue,
+            sc_desc,
+            size,
+            pipelines: PipelineRegistry::new(),
+            pipeline_layout,
+            shaders,
+            mesh,
+            quad,
+            instanced_meshes: Vec::new(),
+            texture,
+            texture_bind_group_layout,
+            texture_bind_group,
+            depth: None,
+            sample_count: 1,
+            multisample: None,
+            errors,
+            screenshot_path: None,
+            recorder: None,
+            elapsed: Duration::from_secs(0),
+            globals,
+            camera,
+            camera_controller: CameraController::new(CameraMode::Orbit),
+            frame: 0,
+            playground_path: None,
+            hot_reload: None,
+        };
+
+        state
+            .add_pipeline(
+                "vertex_buffer_triangle",
+                RenderPipelineBuilder::new("Render Pipeline")
+                    .vertex_shader(BuiltinShader::ShaderVert.name())
+                    .fragment_shader(BuiltinShader::ShaderFrag.name())
+                    .vertex_buffer(Vertex::desc())
+                    // We set the format to the `swap_chain` format so it's easy to copy to it
+                    .color_target(format),
+                VertexSource::VertexBuffer,
+            )
+            .unwrap();
+        // Same as above, but with the `FLAT_COLOR` variant of `shader.frag` from `src/shaders/manifest.toml`
+        let flat_color = BuiltinShader::ShaderFrag
+            .variant(&[("FLAT_COLOR", "")])
+            .expect("shader.frag[FLAT_COLOR] is missing from src/shaders/manifest.toml");
+        state
+            .add_pipeline(
+                "flat_color_triangle",
+                RenderPipelineBuilder::new("Flat Color Pipeline")
+                    .vertex_shader(BuiltinShader::ShaderVert.name())
+                    .fragment_shader(flat_color.name)
+                    .vertex_buffer(Vertex::desc())
+                    .color_target(format),
+                VertexSource::VertexBuffer,
+            )
+            .unwrap();
+        // `shader2.vert` has its triangle hard coded and takes no vertex inputs, so there's no vertex buffer
+        state
+            .add_pipeline(
+                "hard_coded_triangle",
+                RenderPipelineBuilder::new("Render Pipeline 2")
+                    .vertex_shader(BuiltinShader::Shader2Vert.name())
+                    .fragment_shader(BuiltinShader::Shader2Frag.name())
+                    .color_target(format),
+                VertexSource::Procedural { vertex_count: 3 },
+            )
+            .unwrap();
+        // The `TEXTURED` variants of both shaders, drawing `State::texture` onto a quad
+        let textured = |shader: BuiltinShader| {
+            shader.variant(&[("TEXTURED", "")]).unwrap_or_else(|| {
+                panic!(
+                    "{}[TEXTURED] is missing from src/shaders/manifest.toml",
+                    shader.name()
+                )
+            })
+        };
+        state
+            .add_pipeline(
+                "textured_quad",
+                RenderPipelineBuilder::new("Textured Pipeline")
+                    .vertex_shader(textured(BuiltinShader::ShaderVert).name)
+                    .fragment_shader(textured(BuiltinShader::ShaderFrag).name)
+                    .vertex_buffer(TexturedVertex::desc())
+                    .color_target(format),
+                VertexSource::TexturedQuad,
+            )
+            .unwrap();
+        // `triangle.wgsl` has both stages in one module, so the entry points are what tell them apart
+        let wgsl = BuiltinShader::TriangleWgsl;
+        state
+            .add_pipeline(
+                "wgsl_triangle",
+                RenderPipelineBuilder::new("WGSL Pipeline")
+                    .vertex_shader(wgsl.name())
+                    .vertex_entry_point(wgsl.entry_point(ShaderStage::Vertex).unwrap())
+                    .fragment_shader(wgsl.name())
+                    .fragment_entry_point(wgsl.entry_point(ShaderStage::Fragment).unwrap())
+                    .color_target(format),
+                VertexSource::Procedural { vertex_count: 3 },
+            )
+            .unwrap();
+        // The `CAMERA` variant of `shader.vert` puts the triangle in the world, so it can be looked at from any side
+        let camera = BuiltinShader::ShaderVert
+            .variant(&[("CAMERA", "")])
+            .expect("shader.vert[CAMERA] is missing from src/shaders/manifest.toml");
+        state
+            .add_pipeline(
+                "camera_triangle",
+                RenderPipelineBuilder::new("Camera Pipeline")
+                    .vertex_shader(camera.name)
+                    .fragment_shader(BuiltinShader::ShaderFrag.name())
+                    .vertex_buffer(Vertex::desc())
+                    .color_target(format)
+                    // The back of the triangle is just as interesting as the front once the camera can get behind it
+                    .cull_mode(wgpu::CullMode::None)
+                    .depth_test(wgpu::CompareFunction::Less),
+                VertexSource::VertexBuffer,
+            )
+            .unwrap();
+        // The `INSTANCED` variant reads a model matrix and a color for every copy, from the `Instance` buffer in slot 1
+        let instanced = BuiltinShader::ShaderVert
+            .variant(&[("INSTANCED", "")])
+            .expect("shader.vert[INSTANCED] is missing from src/shaders/manifest.toml");
+        state
+            .add_pipeline(
+                "instanced",
+                RenderPipelineBuilder::new("Instanced Pipeline")
+                    .vertex_shader(instanced.name)
+                    .fragment_shader(BuiltinShader::ShaderFrag.name())
+                    .vertex_buffer(Vertex::desc())
+                    .vertex_buffer(Instance::desc())
+                    .color_target(format)
+                    .cull_mode(wgpu::CullMode::None)
+                    .depth_test(wgpu::CompareFunction::Less),
+                VertexSource::Instanced,
+            )
+            .unwrap();
+
+        state
+    }
+
+    // To allow window resizing, we need to recreate the swap chain with the new size
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        // A recording keeps drawing into a texture of the size that it started at, which the depth buffer and the
+        // multisampled texture below wouldn't match anymore, and a Y4M video can't change size partway through,
+        // so the recording ends here
+        let size_changed =
+            (new_size.width, new_size.height) != (self.sc_desc.width, self.sc_desc.height);
+        if self.recorder.is_some() && size_changed {
+            match self.stop_recording() {
+                Ok(frames) => println!(
+                    "The window was resized, stopped recording after {} frames",
+                    frames
+                ),
+                Err(e) => eprintln!("The window was resized, failed to stop recording: {}", e),
+            }
+        }
+        // Update current stored size with new size of resized window
+        self.size = new_size;
+        // Then update size of window in the swap chain descriptor
+        self.sc_desc.width = new_size.width;
+        self.sc_desc.height = new_size.height;
+        match &mut self.target {
+            // Then create a new swap chain based on the updated swap chain descriptor size
+            RenderTarget::Window {
+                surface,
+                swap_chain,
+            } => *swap_chain = self.device.create_swap_chain(surface, &self.sc_desc),
+            // Or a new offscreen texture if we're headless
+            RenderTarget::Offscreen { texture } => {
+                *texture = Self::create_offscreen_texture(&self.device, &self.sc_desc)
+            }
+        }
+        // The depth buffer and the multisampled texture have to stay the same size as the frame
+        self.recreate_attachments();
+        // And the camera has to stretch its view to the new shape
+        self.camera.resize(new_size.width, new_size.height);
+    }
+    // Checks if an event is fully complete, returns bool, if true, main won't process it any longer
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        self.camera_controller.window_event(event)
+    }
+    // Events that aren't tied to the window, like raw mouse movement for the fly camera
+    pub fn device_input(&mut self, event: &DeviceEvent) {
+        self.camera_controller.device_event(event);
+    }
+    // Moves simulated time forward by `dt` and the camera along with its controller
+    // Also picks up any shaders that changed, if hot reloading is enabled
+    pub fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+        self.camera_controller.update_camera(&mut self.camera, dt);
+        self.reload_changed_shaders();
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn camera_mode(&self) -> CameraMode {
+        self.camera_controller.mode()
+    }
+
+    // Swaps the controller, any input that the old one had collected is dropped
+    // Grabbing the cursor for `CameraMode::Fly` is up to whoever owns the window
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        if mode != self.camera_mode() {
+            self.camera_controller = CameraController::new(mode);
+        }
+    }
+
+    // How much simulated time has passed since the state was created
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    // Basically wgpu
+    pub fn render(&mut self, batch: &Batch) -> Result<(), wgpu::SwapChainError> {
+        // We need to get a frame to render to at first
+        // Includes `wgpu::Texture` and `wgpu::Textureview` that holds the image that is being drawn
+        // Remember the `?` operator here means return `Some(thing)` or return `Error`
+        let frame = match &self.target {
+            RenderTarget::Window { swap_chain, .. } => {
+                Frame::SwapChain(swap_chain.get_current_frame()?.output)
+            }
+            RenderTarget::Offscreen { texture } => {
+                Frame::Offscreen(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            }
+        };
+        // Upload this frame's globals, `write_buffer` happens before the commands that we're about to submit
+        self.globals.update(
+            &self.queue,
+            &Globals {
+                resolution: [self.sc_desc.width as f32, self.sc_desc.height as f32],
+                cursor: [
+                    batch.cursor_position.0 as f32,
+                    batch.cursor_position.1 as f32,
+                ],
+                mouse: playground::mouse(batch, self.sc_desc.height),
+                time: self.elapsed.as_secs_f32(),
+                frame: self.frame,
+                _padding: [0; 2],
+                view_proj: self.camera.view_projection(),
+            },
+        );
+        self.frame = self.frame.wrapping_add(1);
+        // Like the globals, the instances that changed get written before this frame's commands run
+        for instanced in &mut self.instanced_meshes {
+            instanced.upload(&self.device, &self.queue);
+        }
+
+        // Recall that the `device` is responsible for creating commands to be sent to the `queue` of the GPU
+        // `encoder` builds this command buffer that is sent to GPU
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+        self.draw_scene(&mut encoder, frame.view(), batch);
+
+        // Swap chain textures can't be copied from, so for a screenshot we draw the same scene a second time into a texture that can
+        let screenshot = self.screenshot_path.take().map(|path| {
+            let texture = Self::create_offscreen_texture(&self.device, &self.sc_desc);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.draw_scene(&mut encoder, &view, batch);
+            // Every format that we can pick for the swap chain is 4 bytes per pixel
+            let dimensions =
+                capture::BufferDimensions::new(self.sc_desc.width, self.sc_desc.height, 4);
+            let buffer = capture::create_readback_buffer(&self.device, &dimensions);
+            capture::copy_texture_to_buffer(&mut encoder, &texture, &buffer, &dimensions);
+            (path, buffer, dimensions)
+        });
+
+        // Recording does the same thing as a screenshot, but every frame, and into the recorder's ring of buffers
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.prepare_frame(&self.device) {
+                eprintln!("Failed to write recorded frame, stopping recording: {}", e);
+                self.recorder = None;
+            }
+        }
+        if let Some(recorder) = &self.recorder {
+            self.draw_scene(&mut encoder, &recorder.create_view(), batch);
+            recorder.copy_frame(&mut encoder);
+        }
+        // Queue accepts anything that implements IntoIter
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame_submitted(&self.device);
+        }
+
+        if let Some((path, buffer, dimensions)) = screenshot {
+            let saved = capture::read_buffer(&self.device, &buffer, &dimensions)
+                .map_err(capture::CaptureError::from)
+                .and_then(|pixels| {
+                    capture::save_png(&path, &dimensions, self.sc_desc.format, pixels)
+                });
+            match saved {
+                Ok(_) => println!("Saved screenshot to {}", path.display()),
+                Err(e) => eprintln!("Failed to save screenshot: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Records a render pass that draws everything in the scene into `view`
+    fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        batch: &Batch,
+    ) {
+        // The pipeline that the batch picked, if there is one
+        let active = self.pipelines.get(batch.active_pipeline);
+        // Only pipelines with a depth test get the depth buffer, wgpu doesn't let the others be drawn into a pass with one
+        let depth_stencil_attachment = match (active, &self.depth) {
+            (Some(active), Some(depth)) if active.builder.depth_format().is_some() => {
+                Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: &depth.view,
+                    // Everything starts out as far away as it can be
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                })
+            }
+            _ => None,
+        };
+
+        // With MSAA everything is drawn into the multisampled texture, and then resolved into `view` at the end of the pass
+        let (attachment, resolve_target) = match &self.multisample {
+            Some(multisample) => (&multisample.view, Some(view)),
+            None => (view, None),
+        };
+
+        // Create a render pass using the encoder
+        // It mutably borrows `encoder` until it's dropped at the end of this function
+        // `RenderPassDescriptor` only has two fields, `color_attachments` and `depth_stencil_attachment`
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            // Describe where the color is going to be drawn to
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                // Informs the texture to which the colors are going to be saved to
+                // Passing in the view of the swap chain frame means we're drawing to the screen
+                attachment,
+                // The texture that will receive the resolved output, same as `attachment` unless multisampling (MSAA) is enabled
+                resolve_target,
+                // What to do with colors on the screen?
+                ops: wgpu::Operations {
+                    // How to handle colors stored from the previous frame
+                    // Currently we're just clearing the colors
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        // Rgb based off of batch TROLL
+                        r: batch.cursor_position.0 % 1.0,
+                        g: batch.cursor_position.1 % 1.0,
+                        b: (batch.cursor_position.0 + batch.cursor_position.1) % 1.0,
+                        a: 1.0,
+                    }),
+                    store: true,
+                },
+            }],
+            // Depth is how far away each pixel is, so that things behind other things get hidden (see `texture::DepthTexture`)
+            depth_stencil_attachment,
+        });
+
+        // Set render pipeline to the one that the batch picked
+        let active = match active {
+            Some(active) => active,
+            // Nothing to draw with, so just clear the screen
+            None => return,
+        };
+        render_pass.set_pipeline(&active.pipeline);
+        render_pass.set_bind_group(0, &self.globals.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+
+        match active.vertex_source {
+            // Binds the mesh's vertex (and index) buffer and draws all of it
+            VertexSource::VertexBuffer => self.mesh.draw(&mut render_pass, 0..1),
+            VertexSource::TexturedQuad => self.quad.draw(&mut render_pass, 0..1),
+            // The shader knows where its vertices are, we only have to say how many
+            VertexSource::Procedural { vertex_count } => render_pass.draw(0..vertex_count, 0..1),
+            VertexSource::Instanced => {
+                for instanced in &self.instanced_meshes {
+                    instanced.draw(&mut render_pass);
+                }
+            }
+        }
+    }
+
+    // Replaces what pipelines with `VertexSource::VertexBuffer` draw, the triangle from `VERTICES` by default
+    pub fn set_mesh(&mut self, mesh: Mesh) {
+        self.mesh = mesh;
+    }
+
+    // Adds a mesh for pipelines with `VertexSource::Instanced` to draw, with no instances yet
+    // Returns its index, for `instanced_mesh_mut`
+    pub fn add_instanced_mesh(&mut self, label: &str, mesh: Mesh) -> usize {
+        self.instanced_meshes.push(InstancedMesh::new(label, mesh));
+        self.instanced_meshes.len() - 1
+    }
+
+    pub fn instanced_meshes(&self) -> &[InstancedMesh] {
+        &self.instanced_meshes
+    }
+
+    // Instances can be added, updated and removed through this, and get uploaded the next time a frame is rendered
+    pub fn instanced_mesh_mut(&mut self, index: usize) -> Option<&mut InstancedMesh> {
+        self.instanced_meshes.get_mut(index)
+    }
+
+    // Replaces the texture that every pipeline gets at group 1, a checkerboard by default
+    pub fn set_texture(&mut self, texture: Texture) {
+        self.texture_bind_group =
+            texture.create_bind_group(&self.device, &self.texture_bind_group_layout);
+        self.texture = texture;
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    // Loads a PNG or JPEG and makes it the texture, see `set_texture`
+    pub fn load_texture(&mut self, path: &Path) -> Result<(), TextureError> {
+        let texture = Texture::from_path(&self.device, &self.queue, path)?;
+        self.set_texture(texture);
+        Ok(())
+    }
+
+    // Compiles the Shadertoy style fragment shader at `path` and registers a fullscreen pipeline for it (see `src/playground.rs`)
+    // Loading another shader replaces the previous playground, returns the index of the pipeline
+    pub fn load_playground(&mut self, path: &Path) -> Result<usize, ShaderError> {
+        let compiled = playground::compile(path)?;
+        self.shaders
+            .insert_compiled(&self.device, playground::FRAGMENT_SHADER_NAME, &compiled);
+        self.playground_path = Some(path.to_path_buf());
+        if let Some(hot_reload) = &mut self.hot_reload {
+            hot_reload.watcher.watch(
+                playground::FRAGMENT_SHADER_NAME,
+                path.to_path_buf(),
+                compiled.includes(),
+                playground::compile,
+            );
+        }
+        let index = self
+            .add_pipeline(
+                playground::PIPELINE_NAME,
+                RenderPipelineBuilder::new("Playground Pipeline")
+                    .vertex_shader(playground::VERTEX_SHADER_NAME)
+                    .fragment_shader(playground::FRAGMENT_SHADER_NAME)
+                    .color_target(self.sc_desc.format),
+                VertexSource::Procedural { vertex_count: 3 },
+            )
+            // Both shaders are definitely in the library by now
+            .unwrap();
+        Ok(index)
+    }
+
+    // Builds a pipeline with the shared pipeline layout and registers it as `name`, replacing any pipeline with that name
+    // The builder is kept, so that the pipeline can be rebuilt when one of its shaders gets reloaded
+    pub fn add_pipeline(
+        &mut self,
+        name: &str,
+        builder: RenderPipelineBuilder<'static>,
+        vertex_source: VertexSource,
+    ) -> Result<usize, PipelineError> {
+        // Every pipeline draws into the same attachments, so they all need the same number of samples as them
+        let builder = builder.sample_count(self.sample_count);
+        // The first pipeline with a depth test is what makes the depth buffer
+        if let Some(format) = builder.depth_format() {
+            if format != texture::DEPTH_FORMAT {
+                return Err(PipelineError::DepthFormat(format));
+            }
+            if self.depth.is_none() {
+                self.depth = Some(DepthTexture::new(
+                    &self.device,
+                    self.sc_desc.width,
+                    self.sc_desc.height,
+                    self.sample_count,
+                ));
+            }
+        }
+        let pipeline = builder
+            .clone()
+            .layout(&self.pipeline_layout)
+            .build(&self.device, &self.shaders)?;
+        Ok(self
+            .pipelines
+            .register(name, builder, pipeline, vertex_source))
+    }
+
+    // Starts watching the shader sources in `src/` (and the playground shader, if there is one) for changes
+    // Changed shaders get recompiled in `update`, and the pipelines that use them are rebuilt in place
+    // Meant for development, the sources have to still be where they were when the program was built
+    pub fn enable_hot_reload(&mut self) {
+        if self.hot_reload.is_some() {
+            return;
+        }
+        let mut watcher = ShaderWatcher::new();
+        for shader in BuiltinShader::ALL
+            .iter()
+            .filter(|shader| shader.is_supported())
+        {
+            // The shader's own module has its first entry point
+            let (_, entry_point) = shader.entry_points()[0];
+            // What the shader included when it was built, so that editing shared code reloads it as well
+            let includes = shader.include_paths();
+            watcher.watch(
+                shader.name(),
+                shader.source_path(),
+                &includes,
+                move |path| shader::compile_file(path, entry_point, &[]),
+            );
+            // Variants and the other entry points come from the same file, so they get recompiled along with it
+            for variant in shader.variants() {
+                watcher.watch(variant.name, shader.source_path(), &includes, move |path| {
+                    shader::compile_file(path, entry_point, variant.defines)
+                });
+            }
+            for entry_point in shader.entry_point_modules() {
+                watcher.watch(
+                    entry_point.module,
+                    shader.source_path(),
+                    &includes,
+                    move |path| shader::compile_file(path, entry_point.name, &[]),
+                );
+            }
+        }
+        if let Some(path) = &self.playground_path {
+            // Which files it includes only comes out of compiling it, the prelude includes `globals.glsl` for one
+            let includes = playground::compile(path)
+                .map(|compiled| compiled.includes().to_vec())
+                .unwrap_or_default();
+            watcher.watch(
+                playground::FRAGMENT_SHADER_NAME,
+                path.clone(),
+                &includes,
+                playground::compile,
+            );
+        }
+        self.hot_reload = Some(HotReload { watcher });
+    }
+
+    fn reload_changed_shaders(&mut self) {
+        let reloads = match &mut self.hot_reload {
+            Some(hot_reload) => hot_reload.watcher.poll(),
+            None => return,
+        };
+        for Reload { name, path, result } in reloads {
+            // Anything that goes wrong leaves the last good shader and pipelines in place
+            let result = result
+                .map_err(|e| e.to_string())
+                .and_then(|compiled| self.replace_shader(&name, &compiled));
+            match result {
+                Ok(rebuilt) => println!(
+                    "Reloaded {}, rebuilt {} pipeline(s)",
+                    path.display(),
+                    rebuilt
+                ),
+                Err(e) => eprintln!(
+                    "Failed to reload {}, keeping the last good version: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    // Swaps in a new module for the shader called `name`, and rebuilds every pipeline that uses it
+    // Either all of them are rebuilt, or nothing changes at all
+    // Returns how many pipelines were rebuilt
+    fn replace_shader(&mut self, name: &str, compiled: &CompiledShader) -> Result<usize, String> {
+        if self.hot_reload.is_none() {
+            return Ok(0);
+        }
+        let errors = self.errors.clone();
+        let join = |errors: Vec<String>| errors.join("\n");
+
+        let device = &self.device;
+        let shader = errors
+            .catch(|| LoadedShader::new(device, name, compiled.source()))
+            .map_err(join)?;
+        let previous = self.shaders.insert(name, shader);
+
+        // Build everything first, so that one bad pipeline doesn't leave the others half updated
+        let (device, layout, shaders) = (&self.device, &self.pipeline_layout, &self.shaders);
+        let rebuilt = self
+            .pipelines
+            .iter_mut()
+            .filter(|entry| entry.builder.uses_shader(name))
+            .map(|entry| {
+                let builder = entry.builder.clone().layout(layout);
+                let pipeline = errors
+                    .catch(|| builder.build(device, shaders))
+                    .map_err(join)?
+                    .map_err(|e| e.to_string())?;
+                Ok((entry, pipeline))
+            })
+            .collect::<Result<Vec<_>, String>>();
+
+        match rebuilt {
+            Ok(rebuilt) => {
+                let count = rebuilt.len();
+                for (entry, pipeline) in rebuilt {
+                    entry.pipeline = pipeline;
+                }
+                Ok(count)
+            }
+            Err(e) => {
+                // Put the old module back, so that rebuilding for some other reason later doesn't pick up the broken one
+                match previous {
+                    Some(previous) => {
+                        self.shaders.insert(name, previous);
+                    }
+                    None => {
+                        self.shaders.remove(name);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Turns on MSAA with `count` samples per pixel (or off, with 1), which smooths out the jagged edges of triangles and lines
+    // Every registered pipeline is rebuilt with the new count, and if anything goes wrong nothing changes at all
+    pub fn set_sample_count(&mut self, count: u32) -> Result<(), PipelineError> {
+        if !texture::SAMPLE_COUNTS.contains(&count) {
+            return Err(PipelineError::InvalidSampleCount(count));
+        }
+        if count == self.sample_count {
+            return Ok(());
+        }
+        let unsupported = |errors: Vec<String>| PipelineError::UnsupportedSampleCount {
+            count,
+            errors: errors.join("\n"),
+        };
+        // Not having MSAA always works
+        if count > 1 {
+            self.errors
+                .catch(|| self.try_sample_count(count))
+                .map_err(unsupported)?;
+        }
+
+        // Build everything first, so that one bad pipeline doesn't leave the others with a different count
+        let (device, layout, shaders, errors) = (
+            &self.device,
+            &self.pipeline_layout,
+            &self.shaders,
+            &self.errors,
+        );
+        let rebuilt = self
+            .pipelines
+            .iter_mut()
+            .map(|entry| {
+                let builder = entry.builder.clone().sample_count(count);
+                let pipeline = errors
+                    .catch(|| builder.clone().layout(layout).build(device, shaders))
+                    .map_err(unsupported)??;
+                Ok((entry, builder, pipeline))
+            })
+            .collect::<Result<Vec<_>, PipelineError>>()?;
+        for (entry, builder, pipeline) in rebuilt {
+            entry.builder = builder;
+            entry.pipeline = pipeline;
+        }
+
+        self.sample_count = count;
+        self.recreate_attachments();
+        Ok(())
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // wgpu only checks the sample count against what the adapter supports when a render pass uses it
+    // So this draws nothing into a 1x1 pass with both a color and a depth attachment, which errors if the count is unsupported
+    fn try_sample_count(&self, count: u32) {
+        let format = self.sc_desc.format;
+        let color = MultisampleTexture::new(&self.device, format, 1, 1, count);
+        let depth = DepthTexture::new(&self.device, 1, 1, count);
+        let resolve = Self::create_offscreen_texture(
+            &self.device,
+            &wgpu::SwapChainDescriptor {
+                width: 1,
+                height: 1,
+                ..self.sc_desc.clone()
+            },
+        );
+        let resolve = resolve.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Sample Count Encoder"),
+            });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sample Count Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &color.view,
+                resolve_target: Some(&resolve),
+                ops: wgpu::Operations::default(),
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &depth.view,
+                depth_ops: Some(wgpu::Operations::default()),
+                stencil_ops: None,
+            }),
+        });
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Makes the depth buffer (if there is one) and the multisampled texture (if MSAA is on) match the frame again
+    fn recreate_attachments(&mut self) {
+        if self.depth.is_some() {
+            self.depth = Some(DepthTexture::new(
+                &self.device,
+                self.sc_desc.width,
+                self.sc_desc.height,
+                self.sample_count,
+            ));
+        }
+        self.multisample = if self.sample_count > 1 {
+            Some(MultisampleTexture::new(
+                &self.device,
+                self.sc_desc.format,
+                self.sc_desc.width,
+                self.sc_desc.height,
+                self.sample_count,
+            ))
+        } else {
+            None
+        };
+    }
+
+    // Every pipeline that can be drawn with, `Batch::active_pipeline` is an index into this
+    pub fn pipelines(&self) -> &PipelineRegistry {
+        &self.pipelines
+    }
+
+    pub fn pipelines_mut(&mut self) -> &mut PipelineRegistry {
+        &mut self.pipelines
+    }
+
+    // Saves the next frame that gets rendered to a PNG at `path`
+    pub fn request_screenshot(&mut self, path: PathBuf) {
+        self.screenshot_path = Some(path);
+    }
+
+    // Starts capturing every rendered frame at the current size, stopping any recording that was already going
+    // Resizing stops the recording as well, see `resize`
+    // While recording, `fixed_timestep` tells the caller how far to move `update` forward each frame
+    pub fn start_recording(
+        &mut self,
+        output: RecordingOutput,
+        fps: u32,
+    ) -> Result<(), capture::CaptureError> {
+        self.stop_recording()?;
+        let texture = Self::create_offscreen_texture(&self.device, &self.sc_desc);
+        self.recorder = Some(Recorder::new(
+            &self.device,
+            texture,
+            self.sc_desc.width,
+            self.sc_desc.height,
+            self.sc_desc.format,
+            output,
+            fps,
+        )?);
+        Ok(())
+    }
+
+    // Writes out the remaining frames, returns how many frames were recorded (0 if we weren't recording)
+    pub fn stop_recording(&mut self) -> Result<u64, capture::CaptureError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(&self.device),
+            None => Ok(0),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // The simulated time between two frames, if it's fixed (which it is while recording)
+    pub fn fixed_timestep(&self) -> Option<Duration> {
+        self.recorder.as_ref().map(Recorder::frame_duration)
+    }
+
+    // Copies the last frame that was rendered offscreen back to the CPU
+    // The pixels are tightly packed rows in `sc_desc.format`, top row first
+    // Returns `None` when rendering to a window, since swap chain textures can't be copied from
+    pub fn read_pixels(&self) -> Option<Result<Vec<u8>, wgpu::BufferAsyncError>> {
+        let texture = match &self.target {
+            RenderTarget::Offscreen { texture } => texture,
+            RenderTarget::Window { .. } => return None,
+        };
+        let dimensions = capture::BufferDimensions::new(
+            self.sc_desc.width,
+            self.sc_desc.height,
+            capture::bytes_per_pixel(self.sc_desc.format)?,
+        );
+        let buffer = capture::create_readback_buffer(&self.device, &dimensions);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        capture::copy_texture_to_buffer(&mut encoder, texture, &buffer, &dimensions);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        Some(capture::read_buffer(&self.device, &buffer, &dimensions))
+    }
+}
@@ -0,0 +1,296 @@
+// Images on the GPU that shaders can sample, bound at `layout(set=1, binding=0)` with their sampler at `binding=1`
+//
+// In GLSL they're declared as
+//
+// layout(set=1, binding=0) uniform texture2D t_diffuse;
+// layout(set=1, binding=1) uniform sampler s_diffuse;
+//
+// and sampled with `texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords)`
+use std::path::Path;
+
+// Everything that can go wrong when loading an image into a texture
+#[derive(Debug)]
+pub enum TextureError {
+    // Couldn't read the file
+    Io(std::io::Error),
+    // The file isn't a PNG or JPEG that we could decode
+    Image(image::ImageError),
+    // Wider or taller than `MAX_TEXTURE_SIZE`
+    TooLarge { width: u32, height: u32, max: u32 },
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::Io(e) => write!(f, "failed to read image: {}", e),
+            TextureError::Image(e) => write!(f, "failed to decode image: {}", e),
+            TextureError::TooLarge { width, height, max } => write!(
+                f,
+                "image is {}x{}, but textures can't be bigger than {}x{}",
+                width, height, max, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(e: std::io::Error) -> Self {
+        TextureError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        TextureError::Image(e)
+    }
+}
+
+// The biggest width and height that a 2D texture is guaranteed to be able to have, WebGPU's default `maxTextureDimension2D`
+// wgpu 0.7's `Limits` doesn't have it yet and doesn't check it either, a bigger texture is up to the backend to reject
+pub const MAX_TEXTURE_SIZE: u32 = 8192;
+
+// A texture, the view that shaders read it through and how it gets sampled
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    // Images are stored as sRGB, so that the shader gets linear colors out of `texture()`
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // Loads a PNG or JPEG file, the format is worked out from the contents rather than the extension
+    pub fn from_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+    ) -> Result<Self, TextureError> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(device, queue, &bytes, &path.display().to_string())
+    }
+
+    // Same as `from_path`, for images that are already in memory, e.g. from `include_bytes!`
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self, TextureError> {
+        let image = image::load_from_memory(bytes)?;
+        Self::from_image(device, queue, &image.to_rgba8(), label)
+    }
+
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::RgbaImage,
+        label: &str,
+    ) -> Result<Self, TextureError> {
+        let (width, height) = image.dimensions();
+        if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(TextureError::TooLarge {
+                width,
+                height,
+                max: MAX_TEXTURE_SIZE,
+            });
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            // `SAMPLED` to read it in shaders, `COPY_DST` to get the pixels into it
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        // Unlike copying from a buffer, `write_texture` doesn't need the rows padded out to 256 bytes
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            image.as_raw(),
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * width,
+                rows_per_image: height,
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            // Texture coordinates outside of 0 to 1 repeat the image, so sprites can be tiled
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            // Blend between the nearest pixels when the texture is drawn bigger than it is
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+            width,
+            height,
+        })
+    }
+
+    // A black and magenta checkerboard, which is what gets drawn until a real texture is set
+    // `size` pixels across, with squares that are `square` pixels wide
+    pub fn checkerboard(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u32,
+        square: u32,
+    ) -> Self {
+        let image = image::RgbaImage::from_fn(size, size, |x, y| {
+            if ((x / square) ^ (y / square)) & 1 == 0 {
+                image::Rgba([255, 0, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+        Self::from_image(device, queue, &image, "Checkerboard Texture")
+            .expect("The checkerboard is too big to be a texture")
+    }
+
+    // The bind group that gives the shaders this texture, made with `create_bind_group_layout`
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+}
+
+// Describes what the shaders can expect to find at group 1, a texture and then its sampler
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Texture Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // Only fragment shaders sample textures
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                // `filtering` because the sampler is `Linear`
+                ty: wgpu::BindingType::Sampler {
+                    filtering: true,
+                    comparison: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+// The format of `State`'s depth buffer, which pipelines with `RenderPipelineBuilder::depth_test` get drawn with
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// How far away from the camera every pixel drawn so far is, 0 at the near plane and 1 at the far plane
+// It has to be the same size as the frame, so it gets recreated whenever the window is resized
+// With MSAA it needs a depth per sample too, so its `sample_count` has to match the color attachment's
+pub struct DepthTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl DepthTexture {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            // Only ever used as an attachment, nothing reads it afterwards
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+}
+
+// The sample counts that MSAA can be set to, 1 being no MSAA at all
+// wgpu 0.7 has no way of asking which of these an adapter supports, so `State::set_sample_count` tries them out instead
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+// With MSAA every pixel gets several samples, which is more than the frame itself can hold
+// So everything is drawn into this texture first, and the render pass "resolves" (averages) the samples into the frame
+// Like the depth buffer, it has to be the same size as the frame
+pub struct MultisampleTexture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl MultisampleTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisampled Color Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            // Same format as the frame, resolving can't convert between formats
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { texture, view }
+    }
+}
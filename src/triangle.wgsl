@@ -0,0 +1,42 @@
+// `triangle.wgsl`
+// The same kind of hard coded triangle as `shader2.vert`, but written in WGSL, the shading language made for WebGPU
+// Unlike GLSL, one file holds both the vertex and the fragment shader, each stage gets its own entry point function
+// `build.rs` checks it with naga, and wgpu turns it into a module as it is, without going through shaderc
+
+// Builtins, inputs and outputs are globals with attributes, instead of `layout(location=0) in`
+[[builtin(vertex_index)]] var<in> in_vertex_index: u32;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+[[location(0)]] var<out> out_uv: vec2<f32>;
+
+// Same layout as `Globals` in `src/uniforms.rs`, WGSL doesn't have `#include` so it's spelled out here
+// It stops after `frame`, the fields after it don't need to be declared if they aren't used
+[[block]]
+struct Globals {
+    resolution: vec2<f32>;
+    cursor: vec2<f32>;
+    mouse: vec4<f32>;
+    time: f32;
+    frame: u32;
+};
+[[group(0), binding(0)]]
+var globals: Globals;
+
+[[stage(vertex)]]
+fn vs_main() {
+    // Vertex 0 is the top, 1 is the bottom left and 2 is the bottom right, counter clockwise so it isn't culled
+    var left: i32 = i32(in_vertex_index & 1u);
+    var right: i32 = i32(in_vertex_index >> 1u);
+    var x: f32 = f32(right - left) * 0.5;
+    var y: f32 = f32(1 - left - right) - 0.5;
+    out_position = vec4<f32>(x, y, 0.0, 1.0);
+    out_uv = vec2<f32>(x, y) + vec2<f32>(0.5, 0.5);
+}
+
+[[location(0)]] var<in> in_uv: vec2<f32>;
+[[location(0)]] var<out> out_color: vec4<f32>;
+
+[[stage(fragment)]]
+fn fs_main() {
+    var pulse: f32 = 0.5 + 0.5 * cos(globals.time);
+    out_color = vec4<f32>(in_uv, pulse, 1.0);
+}
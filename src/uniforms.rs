@@ -0,0 +1,88 @@
+// Data that every shader gets each frame, bound at `layout(set=0, binding=0)`
+//
+// In GLSL it's declared in `src/shaders/include/globals.glsl` as
+//
+// layout(set=0, binding=0) uniform Globals {
+//   vec2 u_resolution;
+//   vec2 u_cursor;
+//   vec4 u_mouse;
+//   float u_time;
+//   uint u_frame;
+//   mat4 u_view_proj;
+// };
+use wgpu::util::DeviceExt;
+
+// The fields have to be in the same order and with the same padding as the GLSL block (std140 layout)
+// `vec2`s are 8 byte aligned, `vec4`s are 16 byte aligned and the whole block is rounded up to a multiple of 16 bytes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Globals {
+    // Size of the frame in pixels
+    pub resolution: [f32; 2],
+    // Cursor position in pixels, from the top left corner of the window
+    pub cursor: [f32; 2],
+    // Shadertoy's `iMouse`, in pixels from the bottom left corner
+    // `xy` is where the cursor is while the left button is held, `zw` is where it was clicked, negative once it's released
+    pub mouse: [f32; 4],
+    // Simulated seconds since the state was created
+    pub time: f32,
+    // How many frames have been rendered before this one
+    pub frame: u32,
+    // Lines `u_view_proj` up on 16 bytes, which is where std140 puts a `mat4`
+    pub _padding: [u32; 2],
+    // Takes positions in the world to clip space, see `src/camera.rs`
+    pub view_proj: [[f32; 4]; 4],
+}
+
+// The GPU side of `Globals`, the buffer and the bind group that points at it
+pub struct GlobalsBinding {
+    buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl GlobalsBinding {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Globals Buffer"),
+            contents: bytemuck::bytes_of(&Globals::default()),
+            // `COPY_DST` so that `queue.write_buffer` can update it every frame
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        // Describes what the shaders can expect to find in the bind group
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Globals Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // Both vertex and fragment shaders are allowed to read it
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        // The actual resources that get bound
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Globals Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    // Queues up the upload, it happens before any command buffers submitted after this
+    pub fn update(&self, queue: &wgpu::Queue, globals: &Globals) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(globals));
+    }
+}
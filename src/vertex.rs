@@ -0,0 +1,177 @@
+// Vertex structs that know how they're laid out in a vertex buffer, usually through `#[derive(VertexLayout)]`
+// The derive is in the `bottle-water-derive` crate, see there for the attributes that it takes
+pub use bottle_water_derive::VertexLayout;
+
+pub trait VertexLayout: bytemuck::Pod {
+    // Whether the buffer has one element per vertex, or one per instance
+    const STEP_MODE: wgpu::InputStepMode;
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+
+    // Descirbes how a buffer is layed out in memory, and how to map it to the shader's `layout(location=N) in`s
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            // How wide one vertex is, including any padding at the end
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: Self::STEP_MODE,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Field types that the shader can read as they are, `[f32; 3]` is a `vec3`, `[u32; 2]` is a `uvec2` and so on
+pub trait VertexAttributeType {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+// Integer types that the shader reads as floats between 0 and 1 (or -1 and 1 when signed), marked with `#[normalized]`
+// e.g. a color stored as `[u8; 4]`, which is a quarter of the size of `[f32; 4]`
+pub trait NormalizedVertexAttribute {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+macro_rules! vertex_formats {
+    ($trait:ident { $($ty:ty => $format:ident,)* }) => {
+        $(impl $trait for $ty {
+            const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+        })*
+    };
+}
+
+vertex_formats!(VertexAttributeType {
+    f32 => Float,
+    [f32; 2] => Float2,
+    [f32; 3] => Float3,
+    [f32; 4] => Float4,
+    u32 => Uint,
+    [u32; 2] => Uint2,
+    [u32; 3] => Uint3,
+    [u32; 4] => Uint4,
+    i32 => Int,
+    [i32; 2] => Int2,
+    [i32; 3] => Int3,
+    [i32; 4] => Int4,
+    f64 => Double,
+    [f64; 2] => Double2,
+    [f64; 3] => Double3,
+    [f64; 4] => Double4,
+    // The smaller integers only come in pairs and fours
+    [u8; 2] => Uchar2,
+    [u8; 4] => Uchar4,
+    [i8; 2] => Char2,
+    [i8; 4] => Char4,
+    [u16; 2] => Ushort2,
+    [u16; 4] => Ushort4,
+    [i16; 2] => Short2,
+    [i16; 4] => Short4,
+});
+
+vertex_formats!(NormalizedVertexAttribute {
+    [u8; 2] => Uchar2Norm,
+    [u8; 4] => Uchar4Norm,
+    [i8; 2] => Char2Norm,
+    [i8; 4] => Char4Norm,
+    [u16; 2] => Ushort2Norm,
+    [u16; 4] => Ushort4Norm,
+    [i16; 2] => Short2Norm,
+    [i16; 4] => Short4Norm,
+});
+
+// Used by the derive, `#[repr(C)]` starts every field at the first multiple of its alignment after the previous field
+// Alignments are always powers of 2, so rounding up is just clearing the low bits
+#[doc(hidden)]
+pub const fn align_offset(end_of_previous: u64, align: u64) -> u64 {
+    (end_of_previous + align - 1) & !(align - 1)
+}
+
+// `bytemuck::Pod` means that out data is just "Plain Old Data"
+// `bytemuck::Zeroable` means that we can use `std::mem::zeroed()`
+// `VertexLayout` writes `Vertex::desc()` for us, from the `#[location]` of every field
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct Vertex {
+    // layout(location=0) in vec3 a_position
+    #[location(0)]
+    pub position: [f32; 3],
+    // layout(location=1) in vec3 a_color
+    #[location(1)]
+    pub color: [f32; 3],
+}
+
+pub const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [0.0, 0.5, 0.0],
+        color: [1.0, 0.0, 0.0],
+    },
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        color: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        color: [0.0, 0.0, 1.0],
+    },
+];
+
+// A pentagon made of 3 triangles, the 5 corners are shared between them through `PENTAGON_INDICES`
+pub const PENTAGON_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386, 0.0],
+        color: [0.5, 0.0, 0.5],
+    },
+    Vertex {
+        position: [-0.49513406, 0.06958647, 0.0],
+        color: [0.5, 0.0, 0.5],
+    },
+    Vertex {
+        position: [-0.21918549, -0.44939706, 0.0],
+        color: [0.5, 0.0, 0.5],
+    },
+    Vertex {
+        position: [0.35966998, -0.3473291, 0.0],
+        color: [0.5, 0.0, 0.5],
+    },
+    Vertex {
+        position: [0.44147372, 0.2347359, 0.0],
+        color: [0.5, 0.0, 0.5],
+    },
+];
+
+// Counterclockwise, so that none of the triangles get culled
+pub const PENTAGON_INDICES: &[u32] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+// A vertex for drawing textures, `tex_coords` says which part of the texture ends up at this corner
+// Drawn with the `TEXTURED` variants of `shader.vert` and `shader.frag`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+pub struct TexturedVertex {
+    // layout(location=0) in vec3 a_position
+    #[location(0)]
+    pub position: [f32; 3],
+    // layout(location=1) in vec2 a_tex_coords
+    // (0, 0) is the top left corner of the texture and (1, 1) the bottom right, so `y` goes the other way to `position`
+    #[location(1)]
+    pub tex_coords: [f32; 2],
+}
+
+// A square in the middle of the screen that shows the whole texture, the right way up
+pub const QUAD_VERTICES: &[TexturedVertex] = &[
+    TexturedVertex {
+        position: [-0.5, 0.5, 0.0],
+        tex_coords: [0.0, 0.0],
+    },
+    TexturedVertex {
+        position: [-0.5, -0.5, 0.0],
+        tex_coords: [0.0, 1.0],
+    },
+    TexturedVertex {
+        position: [0.5, -0.5, 0.0],
+        tex_coords: [1.0, 1.0],
+    },
+    TexturedVertex {
+        position: [0.5, 0.5, 0.0],
+        tex_coords: [1.0, 0.0],
+    },
+];
+
+// Two counterclockwise triangles
+pub const QUAD_INDICES: &[u32] = &[0, 1, 2, 0, 2, 3];
@@ -0,0 +1,51 @@
+// Where the camera's matrices put points, worked out by hand
+use bottle_water::camera::{self, Camera, Projection};
+
+// Where `point` ends up on the screen, x and y from -1 to 1 and z from 0 to 1
+fn project(camera: &Camera, point: [f32; 3]) -> [f32; 3] {
+    let [x, y, z, w] = camera::transform(
+        &camera.view_projection(),
+        [point[0], point[1], point[2], 1.0],
+    );
+    [x / w, y / w, z / w]
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    let close = actual
+        .iter()
+        .zip(expected)
+        .all(|(a, e)| (a - e).abs() < 1e-4);
+    assert!(close, "expected {:?}, got {:?}", expected, actual);
+}
+
+#[test]
+fn target_is_in_the_middle() {
+    let mut camera = Camera::new(800, 600);
+    camera.eye = [3.0, 2.0, -1.0];
+    camera.target = [0.5, 0.0, 0.5];
+    let projected = project(&camera, camera.target);
+    assert_close(&projected[..2], &[0.0, 0.0]);
+}
+
+#[test]
+fn near_and_far_planes_are_0_and_1() {
+    // The default camera is at z = 2 looking down -z
+    let camera = Camera::new(100, 100);
+    let (near, far) = match camera.projection {
+        Projection::Perspective { near, far, .. } => (near, far),
+        Projection::Orthographic { .. } => panic!("The default camera should be perspective"),
+    };
+    assert_close(&project(&camera, [0.0, 0.0, 2.0 - near]), &[0.0, 0.0, 0.0]);
+    assert_close(&project(&camera, [0.0, 0.0, 2.0 - far]), &[0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn perspective_and_orthographic_agree_at_the_target() {
+    // Toggling the projection keeps things at the target's distance the same size, and stretches x with the aspect ratio
+    let mut camera = Camera::new(200, 100);
+    let perspective = project(&camera, [0.5, 0.5, 0.0]);
+    camera.toggle_projection();
+    let orthographic = project(&camera, [0.5, 0.5, 0.0]);
+    assert_close(&orthographic[..2], &perspective[..2]);
+    assert_close(&[orthographic[0] * 2.0], &[orthographic[1]]);
+}
@@ -0,0 +1,475 @@
+// Golden image tests
+// Each test renders a known scene with the headless `State` and compares it against a reference PNG in `tests/golden/`
+//
+// - A missing reference fails the test, run with `GOLDEN_BLESS=1` to write it and then commit the new PNG
+// - `GOLDEN_BLESS=1` also overwrites all of the existing references after an intentional change
+// - On a mismatch, `<name>.actual.png` and `<name>.diff.png` are written to `target/golden/`
+// - These need an adapter, a software one like lavapipe or SwiftShader is fine
+//   Without one every test fails, unless `GOLDEN_ALLOW_NO_ADAPTER=1` is set to skip them on purpose
+use bottle_water::mesh::{self, MeshError};
+use bottle_water::pipeline::{PipelineError, RenderPipelineBuilder, VertexSource};
+use bottle_water::record::RecordingOutput;
+use bottle_water::texture::{TextureError, MAX_TEXTURE_SIZE};
+use bottle_water::{
+    Batch, BuiltinShader, Instance, InstancedMesh, Mesh, State, Texture, Vertex, VertexLayout,
+    PENTAGON_INDICES, PENTAGON_VERTICES, VERTICES,
+};
+use futures::executor::block_on;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 64;
+// Using an RGBA format means that the pixels we read back are already in the same layout as the PNGs
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+// How far apart (out of 255) a single channel of the actual and reference pixel can be
+// Different adapters rasterize and interpolate slightly differently, especially along triangle edges
+const CHANNEL_TOLERANCE: u8 = 3;
+
+// Only returns `None` if there's no adapter on this machine and `GOLDEN_ALLOW_NO_ADAPTER` is set
+// Otherwise a machine without an adapter would report every test as passing without testing anything
+fn headless_state() -> Option<State> {
+    let state = block_on(State::new_headless(WIDTH, HEIGHT, FORMAT));
+    if state.is_none() {
+        if std::env::var_os("GOLDEN_ALLOW_NO_ADAPTER").is_none() {
+            panic!(
+                "No adapter found, set GOLDEN_ALLOW_NO_ADAPTER=1 to skip the golden image tests"
+            );
+        }
+        eprintln!("No adapter found, skipping golden image test");
+    }
+    state
+}
+
+// Renders one frame with `batch` using the pipeline called `pipeline`, and returns the RGBA pixels
+fn render(pipeline: &str, batch: &mut Batch) -> Option<Vec<u8>> {
+    headless_state().map(|state| render_with(state, pipeline, batch))
+}
+
+fn render_with(mut state: State, pipeline: &str, batch: &mut Batch) -> Vec<u8> {
+    batch.active_pipeline = state
+        .pipelines()
+        .index_of(pipeline)
+        .unwrap_or_else(|| panic!("No pipeline called {}", pipeline));
+    state.update(Duration::from_secs(0));
+    state.render(batch).expect("Failed to render frame");
+    state
+        .read_pixels()
+        .expect("Headless state should support reading pixels")
+        .expect("Failed to read back pixels")
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn output_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden")
+}
+
+fn save_png(path: &PathBuf, pixels: &[u8]) {
+    image::save_buffer(path, pixels, WIDTH, HEIGHT, image::ColorType::Rgba8)
+        .unwrap_or_else(|e| panic!("Failed to write {}: {}", path.display(), e));
+}
+
+// Compares `actual` against `tests/golden/<name>.png`
+fn assert_golden(name: &str, actual: &[u8]) {
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        save_png(&reference_path, actual);
+        eprintln!("Wrote reference image {}", reference_path.display());
+        return;
+    }
+    if !reference_path.exists() {
+        // Saved so that it can be looked at before blessing it
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.actual.png", name));
+        save_png(&actual_path, actual);
+        panic!(
+            "Missing reference image {}, check {} and run with GOLDEN_BLESS=1 to accept it",
+            reference_path.display(),
+            actual_path.display()
+        );
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", reference_path.display(), e))
+        .to_rgba8();
+    assert_eq!(
+        reference.dimensions(),
+        (WIDTH, HEIGHT),
+        "Reference image {} has the wrong size",
+        reference_path.display()
+    );
+
+    // The diff image is a dimmed copy of the actual frame, with every mismatching pixel painted bright red
+    let mut diff = Vec::with_capacity(actual.len());
+    let mut mismatches = 0;
+    for (actual, expected) in actual.chunks(4).zip(reference.as_raw().chunks(4)) {
+        let matches = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (*a as i16 - *e as i16).abs() <= CHANNEL_TOLERANCE as i16);
+        if matches {
+            diff.extend_from_slice(&[actual[0] / 4, actual[1] / 4, actual[2] / 4, 255]);
+        } else {
+            mismatches += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        }
+    }
+
+    if mismatches > 0 {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{}.actual.png", name));
+        let diff_path = output_dir().join(format!("{}.diff.png", name));
+        save_png(&actual_path, actual);
+        save_png(&diff_path, &diff);
+        panic!(
+            "{} of {} pixels differ from {} by more than {}, see {} and {}",
+            mismatches,
+            WIDTH * HEIGHT,
+            reference_path.display(),
+            CHANNEL_TOLERANCE,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn vertex_buffer_triangle() {
+    // The `VERTICES` triangle over a black background
+    if let Some(pixels) = render("vertex_buffer_triangle", &mut Batch::new()) {
+        assert_golden("vertex_buffer_triangle", &pixels);
+    }
+}
+
+#[test]
+fn hard_coded_triangle() {
+    // The triangle that `shader2.vert` makes up from `gl_VertexIndex`
+    if let Some(pixels) = render("hard_coded_triangle", &mut Batch::new()) {
+        assert_golden("hard_coded_triangle", &pixels);
+    }
+}
+
+#[test]
+fn cursor_background() {
+    // The clear color comes from the fractional part of the cursor position
+    let mut batch = Batch::new();
+    batch.cursor_position = (10.25, 20.5);
+    if let Some(pixels) = render("vertex_buffer_triangle", &mut batch) {
+        assert_golden("cursor_background", &pixels);
+    }
+}
+
+#[test]
+fn indexed_pentagon() {
+    // Only 5 vertices, shared between the 3 triangles through the index buffer
+    if let Some(mut state) = headless_state() {
+        let mesh = Mesh::indexed(
+            &state.device,
+            "Pentagon",
+            PENTAGON_VERTICES,
+            PENTAGON_INDICES,
+        )
+        .unwrap();
+        state.set_mesh(mesh);
+        let pixels = render_with(state, "vertex_buffer_triangle", &mut Batch::new());
+        assert_golden("indexed_pentagon", &pixels);
+    }
+}
+
+#[test]
+fn out_of_range_index() {
+    // 16 bit indices would have turned 65541 into 5, and 5 would have read past the end of the vertex buffer
+    if let Some(state) = headless_state() {
+        let indices = [0, 1, 2, 0, 2, u16::MAX as u32 + 6];
+        match Mesh::indexed(&state.device, "Broken", PENTAGON_VERTICES, &indices) {
+            Err(MeshError::IndexOutOfRange {
+                position: 5,
+                index,
+                num_vertices: 5,
+            }) => assert_eq!(index, u16::MAX as u32 + 6),
+            Err(e) => panic!("Expected an out of range index, got: {}", e),
+            Ok(_) => panic!("Expected index {} to be rejected", indices[5]),
+        }
+    }
+    // `0xFFFF` restarts strips, so 16 bit indices stop one vertex short of it
+    assert_eq!(
+        mesh::index_format_for(u16::MAX as usize),
+        wgpu::IndexFormat::Uint16
+    );
+    assert_eq!(
+        mesh::index_format_for(u16::MAX as usize + 1),
+        wgpu::IndexFormat::Uint32
+    );
+}
+
+#[test]
+fn playground_plasma() {
+    // The example Shadertoy style shader on the fullscreen triangle, at time 0
+    if let Some(mut state) = headless_state() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("playground")
+            .join("plasma.frag");
+        state
+            .load_playground(&path)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", path.display(), e));
+        let pixels = render_with(state, "playground", &mut Batch::new());
+        assert_golden("playground_plasma", &pixels);
+    }
+}
+
+#[test]
+fn flat_color_triangle() {
+    // The `FLAT_COLOR` variant of `shader.frag`, built from `src/shaders/manifest.toml`
+    if let Some(pixels) = render("flat_color_triangle", &mut Batch::new()) {
+        assert_golden("flat_color_triangle", &pixels);
+    }
+}
+
+#[test]
+fn wgsl_triangle() {
+    // `triangle.wgsl`, both stages from one WGSL module
+    if let Some(pixels) = render("wgsl_triangle", &mut Batch::new()) {
+        assert_golden("wgsl_triangle", &pixels);
+    }
+}
+
+#[test]
+fn mismatched_varyings() {
+    // `shader2.vert` only writes location 1, but `shader.frag` reads its color from location 0
+    if let Some(mut state) = headless_state() {
+        let result = state.add_pipeline(
+            "mismatched",
+            RenderPipelineBuilder::new("Mismatched Pipeline")
+                .vertex_shader(BuiltinShader::Shader2Vert.name())
+                .fragment_shader(BuiltinShader::ShaderFrag.name())
+                .color_target(FORMAT),
+            VertexSource::Procedural { vertex_count: 3 },
+        );
+        match result {
+            Err(PipelineError::MissingVarying { location: 0, .. }) => {}
+            Err(e) => panic!("Expected a missing varying at location 0, got: {}", e),
+            Ok(_) => panic!("Expected the pipeline to be rejected"),
+        }
+    }
+}
+
+#[test]
+fn textured_quad() {
+    // The `TEXTURED` variants drawing the default checkerboard texture onto `QUAD_VERTICES`
+    if let Some(pixels) = render("textured_quad", &mut Batch::new()) {
+        assert_golden("textured_quad", &pixels);
+    }
+}
+
+#[test]
+fn oversized_texture() {
+    // Too wide to be a texture, which has to be an error instead of a panic from wgpu
+    if let Some(state) = headless_state() {
+        let image = image::RgbaImage::new(MAX_TEXTURE_SIZE + 1, 1);
+        match Texture::from_image(&state.device, &state.queue, &image, "Oversized Texture") {
+            Err(TextureError::TooLarge {
+                width,
+                height: 1,
+                max: MAX_TEXTURE_SIZE,
+            }) => assert_eq!(width, MAX_TEXTURE_SIZE + 1),
+            Err(e) => panic!("Expected the texture to be too large, got: {}", e),
+            Ok(_) => panic!(
+                "Expected a texture wider than {} to be rejected",
+                MAX_TEXTURE_SIZE
+            ),
+        }
+    }
+}
+
+#[test]
+fn blurred_texture() {
+    // The checkerboard stretched over the whole frame, with its columns blurred together by the `blur_x` entry point
+    if let Some(mut state) = headless_state() {
+        let blur = BuiltinShader::PostBlurFrag;
+        state
+            .add_pipeline(
+                "blur_x",
+                RenderPipelineBuilder::new("Blur Pipeline")
+                    .vertex_shader(BuiltinShader::FullscreenVert.name())
+                    .fragment_shader(blur.name())
+                    .fragment_entry_point("blur_x")
+                    .color_target(FORMAT),
+                VertexSource::Procedural { vertex_count: 3 },
+            )
+            .expect("Failed to build a pipeline with the blur_x entry point");
+        let pixels = render_with(state, "blur_x", &mut Batch::new());
+        assert_golden("blurred_texture", &pixels);
+    }
+}
+
+#[test]
+fn depth_tested_triangles() {
+    // The red triangle is in front but gets drawn first, so the blue one only shows where they don't overlap
+    if let Some(mut state) = headless_state() {
+        let triangle = |x: f32, z: f32, color: [f32; 3]| {
+            vec![
+                Vertex {
+                    position: [x, 0.5, z],
+                    color,
+                },
+                Vertex {
+                    position: [x - 0.5, -0.5, z],
+                    color,
+                },
+                Vertex {
+                    position: [x + 0.5, -0.5, z],
+                    color,
+                },
+            ]
+        };
+        let mut vertices = triangle(-0.2, 0.25, [1.0, 0.0, 0.0]);
+        vertices.extend(triangle(0.2, 0.75, [0.0, 0.0, 1.0]));
+        let mesh = Mesh::new(&state.device, "Overlapping Triangles", &vertices);
+        state.set_mesh(mesh);
+        state
+            .add_pipeline(
+                "depth_tested",
+                RenderPipelineBuilder::new("Depth Tested Pipeline")
+                    .vertex_shader(BuiltinShader::ShaderVert.name())
+                    .fragment_shader(BuiltinShader::ShaderFrag.name())
+                    .vertex_buffer(Vertex::desc())
+                    .color_target(FORMAT)
+                    .depth_test(wgpu::CompareFunction::Less),
+                VertexSource::VertexBuffer,
+            )
+            .expect("Failed to build the depth tested pipeline");
+        let pixels = render_with(state, "depth_tested", &mut Batch::new());
+        assert_golden("depth_tested_triangles", &pixels);
+    }
+}
+
+// Resizing has to stop a recording, or the recorder's texture and the resized attachments would have different sizes
+fn resize_while_recording(mut state: State, pipeline: &str, name: &str) {
+    let mut batch = Batch::new();
+    batch.active_pipeline = state.pipelines().index_of(pipeline).unwrap();
+    let dir = std::env::temp_dir().join(format!("bottle-water-{}-{}", name, std::process::id()));
+    state
+        .start_recording(RecordingOutput::ImageSequence(dir.clone()), 30)
+        .expect("Failed to start recording");
+    state.render(&batch).expect("Failed to render frame");
+
+    state.resize(winit::dpi::PhysicalSize::new(WIDTH * 2, HEIGHT));
+    assert!(!state.is_recording());
+    state.render(&batch).expect("Failed to render frame");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn resize_while_recording_with_depth() {
+    if let Some(state) = headless_state() {
+        resize_while_recording(state, "camera_triangle", "recording-depth");
+    }
+}
+
+#[test]
+fn multisampled_triangle() {
+    // The `VERTICES` triangle again, but with 4x MSAA its edges blend into the background instead of being jagged
+    if let Some(mut state) = headless_state() {
+        state
+            .set_sample_count(4)
+            .expect("Every adapter should support 4 samples per pixel");
+        let pixels = render_with(state, "vertex_buffer_triangle", &mut Batch::new());
+        assert_golden("multisampled_triangle", &pixels);
+    }
+}
+
+#[test]
+fn resize_while_recording_with_msaa() {
+    if let Some(mut state) = headless_state() {
+        state
+            .set_sample_count(4)
+            .expect("Every adapter should support 4 samples per pixel");
+        resize_while_recording(state, "vertex_buffer_triangle", "recording-msaa");
+    }
+}
+
+#[test]
+fn invalid_sample_count() {
+    if let Some(mut state) = headless_state() {
+        match state.set_sample_count(3) {
+            Err(PipelineError::InvalidSampleCount(3)) => {}
+            Err(e) => panic!("Expected the sample count to be invalid, got: {}", e),
+            Ok(_) => panic!("Expected 3 samples per pixel to be rejected"),
+        }
+        // Nothing changed, MSAA is still off
+        assert_eq!(state.sample_count(), 1);
+    }
+}
+
+#[test]
+fn camera_triangle() {
+    // The `VERTICES` triangle in the world, seen from above and to the right through the default perspective camera
+    if let Some(mut state) = headless_state() {
+        let camera = state.camera_mut();
+        camera.eye = [1.0, 1.0, 1.5];
+        let pixels = render_with(state, "camera_triangle", &mut Batch::new());
+        assert_golden("camera_triangle", &pixels);
+    }
+}
+
+#[test]
+fn instanced_triangles() {
+    // A 3x3 grid of small `VERTICES` triangles in one draw call, with the middle one removed and a corner one turned red
+    if let Some(mut state) = headless_state() {
+        let mesh = Mesh::new(&state.device, "Instanced Triangle", VERTICES);
+        let index = state.add_instanced_mesh("Triangles", mesh);
+        let triangles = state.instanced_mesh_mut(index).unwrap();
+        let mut ids = Vec::new();
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let position = [x as f32 * 0.5, y as f32 * 0.5, 0.0];
+                ids.push(triangles.add(Instance::at(position, 0.4, [1.0; 4])));
+            }
+        }
+        let corner = Instance::at([-0.5, -0.5, 0.0], 0.4, [1.0, 0.0, 0.0, 1.0]);
+        assert!(triangles.update(ids[0], corner));
+        assert!(triangles.remove(ids[4]).is_some());
+        assert!(triangles.remove(ids[4]).is_none());
+        // The last triangle got moved into the gap, but its id still finds it
+        assert_eq!(triangles.len(), 8);
+        assert_eq!(triangles.get(ids[8]).unwrap().model[3][0], 0.5);
+        let pixels = render_with(state, "instanced", &mut Batch::new());
+        assert_golden("instanced_triangles", &pixels);
+    }
+}
+
+#[test]
+fn instances_are_drawn_as_uploaded() {
+    // Until `upload`, `draw` keeps drawing what's in the buffer instead of instances that aren't in it yet
+    if let Some(state) = headless_state() {
+        let mesh = Mesh::new(&state.device, "Instanced Triangle", VERTICES);
+        let mut triangles = InstancedMesh::new("Triangles", mesh);
+        triangles.add(Instance::at([0.0; 3], 1.0, [1.0; 4]));
+        assert_eq!(triangles.uploaded_len(), 0);
+        triangles.upload(&state.device, &state.queue);
+        assert_eq!(triangles.uploaded_len(), 1);
+
+        triangles.add(Instance::at([0.5, 0.0, 0.0], 1.0, [1.0; 4]));
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles.uploaded_len(), 1);
+        triangles.upload(&state.device, &state.queue);
+        assert_eq!(triangles.uploaded_len(), 2);
+
+        // Removing the last one leaves nothing to write to the buffer, but it still has to stop being drawn
+        let last = triangles.add(Instance::at([-0.5, 0.0, 0.0], 1.0, [1.0; 4]));
+        triangles.upload(&state.device, &state.queue);
+        assert!(triangles.remove(last).is_some());
+        assert_eq!(triangles.uploaded_len(), 3);
+        triangles.upload(&state.device, &state.queue);
+        assert_eq!(triangles.uploaded_len(), 2);
+    }
+}
@@ -0,0 +1,107 @@
+// Recompiling shaders when their sources or the files that they `#include` change, no device needed
+use bottle_water::hot_reload::{Reload, ShaderWatcher};
+use bottle_water::includes;
+use bottle_water::shader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// A fresh directory for each test, so they can run at the same time
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bottle-water-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Rewrites the file and moves its modification time well past what it was
+// Some filesystems only keep it to the second (or two), so a write right after the file was created could look like no change
+fn edit(path: &Path, contents: &str) {
+    let before = std::fs::metadata(path).unwrap().modified().unwrap();
+    std::fs::write(path, contents).unwrap();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(before + Duration::from_secs(10)).unwrap();
+}
+
+// Keeps polling until something gets reloaded, the watcher only looks at the files every so often
+// Gives up after a while so that a broken watcher fails the test instead of hanging it
+fn poll_until_reload(watcher: &mut ShaderWatcher) -> Vec<Reload> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let reloads = watcher.poll();
+        if !reloads.is_empty() || Instant::now() > deadline {
+            return reloads;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+const SHADER: &str = "#version 450
+
+#include \"color.glsl\"
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+  f_color = COLOR;
+}
+";
+
+#[test]
+fn editing_an_include_reloads_the_shader() {
+    let dir = temp_dir("include-reload");
+    let shader_path = dir.join("shader.frag");
+    let include_path = dir.join("color.glsl");
+    std::fs::write(&shader_path, SHADER).unwrap();
+    std::fs::write(&include_path, "#define COLOR vec4(1.0)\n").unwrap();
+
+    let compiled = shader::compile_file(&shader_path, "main", &[]).unwrap();
+    assert_eq!(compiled.includes(), std::slice::from_ref(&include_path));
+
+    let mut watcher = ShaderWatcher::new();
+    watcher.watch("shader.frag", shader_path, compiled.includes(), |path| {
+        shader::compile_file(path, "main", &[])
+    });
+    // Longer than the watcher waits between looking at the files, so this poll actually checks them
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(watcher.poll().is_empty(), "nothing changed yet");
+
+    edit(&include_path, "#define COLOR vec4(0.5)\n");
+    let reloads = poll_until_reload(&mut watcher);
+    assert_eq!(
+        reloads.len(),
+        1,
+        "editing the include didn't reload the shader"
+    );
+    assert_eq!(reloads[0].name, "shader.frag");
+    assert!(reloads[0].result.is_ok());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn globals_are_built_in() {
+    // Without any include directories, like a binary that was copied away from where it was built
+    let mut compiler = shaderc::Compiler::new().unwrap();
+    let mut options = shaderc::CompileOptions::new().unwrap();
+    includes::set_include_callback(&mut options, Vec::new(), |path: &Path| {
+        panic!("{} isn't built in", path.display())
+    });
+    let source = "#version 450
+
+#include <globals.glsl>
+
+layout(location=0) out vec4 f_color;
+
+void main() {
+  f_color = vec4(u_time);
+}
+";
+    let result = compiler.compile_into_spirv(
+        source,
+        shaderc::ShaderKind::Fragment,
+        "globals.frag",
+        "main",
+        Some(&options),
+    );
+    assert!(result.is_ok(), "{}", result.err().unwrap());
+}
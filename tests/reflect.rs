@@ -0,0 +1,188 @@
+// Reflecting the shaders that `build.rs` compiled, and checking pipelines against what comes out, no GPU needed
+use bottle_water::pipeline::{self, PipelineError};
+use bottle_water::reflect::{
+    EntryPointInterface, ScalarKind, ShaderInterface, Varying, VaryingType,
+};
+use bottle_water::shader::{ENTRY_POINTS, GLSL_ENTRY_POINT};
+use bottle_water::{BuiltinShader, Instance, Vertex, VertexLayout};
+
+const FLOAT2: VaryingType = VaryingType {
+    kind: ScalarKind::Float,
+    components: 2,
+};
+const FLOAT3: VaryingType = VaryingType {
+    kind: ScalarKind::Float,
+    components: 3,
+};
+const FLOAT4: VaryingType = VaryingType {
+    kind: ScalarKind::Float,
+    components: 4,
+};
+
+fn reflect(source: wgpu::ShaderSource) -> ShaderInterface {
+    ShaderInterface::reflect(&source).expect("Failed to reflect the shader")
+}
+
+fn main_of(interface: &ShaderInterface) -> &EntryPointInterface {
+    interface
+        .entry_point("main")
+        .expect("The shader has no `main` entry point")
+}
+
+// The SPIR-V of the variant of `shader` that was compiled with `define`
+fn variant(shader: BuiltinShader, define: &str) -> ShaderInterface {
+    let variant = shader
+        .variant(&[(define, "")])
+        .unwrap_or_else(|| panic!("{} has no {} variant", shader.name(), define));
+    reflect(wgpu::util::make_spirv(variant.spirv))
+}
+
+#[test]
+fn vertex_shader_interface() {
+    let interface = reflect(BuiltinShader::ShaderVert.source());
+    let main = main_of(&interface);
+    // `a_position` and `a_color` in, `v_color` out, `gl_Position` is a builtin so it isn't there
+    assert_eq!(main.input(0), Some(FLOAT3));
+    assert_eq!(main.input(1), Some(FLOAT3));
+    assert_eq!(main.inputs.len(), 2);
+    assert_eq!(
+        main.outputs,
+        [Varying {
+            location: 0,
+            ty: FLOAT3
+        }]
+    );
+
+    // `v_tex_coords` instead of `v_color`
+    let textured = variant(BuiltinShader::ShaderVert, "TEXTURED");
+    assert_eq!(main_of(&textured).input(1), Some(FLOAT2));
+    assert_eq!(main_of(&textured).output(0), Some(FLOAT2));
+}
+
+#[test]
+fn instance_inputs() {
+    let interface = variant(BuiltinShader::ShaderVert, "INSTANCED");
+    let main = main_of(&interface);
+    // The 4 columns of the model matrix from location 2, then `a_instance_color` right after them
+    for location in 2..6 {
+        assert_eq!(main.input(location), Some(FLOAT4), "location {}", location);
+    }
+    assert_eq!(main.input(6), Some(FLOAT4));
+    assert_eq!(main.inputs.len(), 7);
+
+    // Which is exactly what `Instance::desc()` has
+    pipeline::validate_vertex_inputs(
+        "shader.vert[INSTANCED]",
+        main,
+        &[Vertex::desc(), Instance::desc()],
+    )
+    .unwrap();
+    match pipeline::validate_vertex_inputs("shader.vert[INSTANCED]", main, &[Vertex::desc()]) {
+        Err(PipelineError::MissingAttribute { location: 2, .. }) => {}
+        other => panic!("Expected the model matrix to be missing, got {:?}", other),
+    }
+}
+
+#[test]
+fn attribute_kind_mismatch() {
+    let interface = reflect(BuiltinShader::ShaderVert.source());
+    let main = main_of(&interface);
+    pipeline::validate_vertex_inputs("shader.vert", main, &[Vertex::desc()]).unwrap();
+
+    // `a_color` is a `vec3`, but the buffer has unsigned ints there
+    let attributes = [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float3,
+            offset: 0,
+            shader_location: 0,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Uint3,
+            offset: 12,
+            shader_location: 1,
+        },
+    ];
+    let layout = wgpu::VertexBufferLayout {
+        array_stride: 24,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &attributes,
+    };
+    match pipeline::validate_vertex_inputs("shader.vert", main, &[layout]) {
+        Err(PipelineError::AttributeTypeMismatch {
+            location: 1,
+            format: wgpu::VertexFormat::Uint3,
+            expected: FLOAT3,
+            ..
+        }) => {}
+        other => panic!("Expected an attribute type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn varying_mismatches() {
+    let vertex = reflect(BuiltinShader::ShaderVert.source());
+    let fragment = reflect(BuiltinShader::ShaderFrag.source());
+    let vertex = ("shader.vert", main_of(&vertex));
+    pipeline::validate_varyings(vertex, ("shader.frag", main_of(&fragment))).unwrap();
+
+    // A fragment shader that reads `v_color` as an `ivec3`
+    let ints = EntryPointInterface {
+        inputs: vec![Varying {
+            location: 0,
+            ty: VaryingType {
+                kind: ScalarKind::Sint,
+                components: 3,
+            },
+        }],
+        outputs: Vec::new(),
+    };
+    match pipeline::validate_varyings(vertex, ("ints.frag", &ints)) {
+        Err(PipelineError::VaryingTypeMismatch {
+            location: 0,
+            output: FLOAT3,
+            ..
+        }) => {}
+        other => panic!("Expected a varying type mismatch, got {:?}", other),
+    }
+
+    // `shader.vert[TEXTURED]` only writes a `vec2` where `shader.frag` reads a `vec3`
+    let textured = variant(BuiltinShader::ShaderVert, "TEXTURED");
+    match pipeline::validate_varyings(
+        ("shader.vert[TEXTURED]", main_of(&textured)),
+        ("shader.frag", main_of(&fragment)),
+    ) {
+        Err(PipelineError::VaryingTypeMismatch {
+            output: FLOAT2,
+            input: FLOAT3,
+            ..
+        }) => {}
+        other => panic!("Expected a varying type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn entry_point_modules_call_their_function_main() {
+    // glslang names every GLSL entry point `main`, whatever the function was called in the source
+    for entry_point in ENTRY_POINTS {
+        let interface = reflect(wgpu::util::make_spirv(entry_point.spirv));
+        assert_eq!(entry_point.module_entry_point, GLSL_ENTRY_POINT);
+        assert!(
+            interface.entry_point(GLSL_ENTRY_POINT).is_some(),
+            "{} has no entry point called {}",
+            entry_point.module,
+            GLSL_ENTRY_POINT
+        );
+        assert!(interface.entry_point(entry_point.name).is_none());
+    }
+
+    // So the modules only differ in which function got compiled
+    let blur = |name: &str| {
+        ENTRY_POINTS
+            .iter()
+            .find(|entry_point| {
+                entry_point.shader == BuiltinShader::PostBlurFrag && entry_point.name == name
+            })
+            .unwrap_or_else(|| panic!("post/blur.frag has no {} module", name))
+    };
+    assert_ne!(blur("blur_x").spirv, blur("blur_y").spirv);
+}
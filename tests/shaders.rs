@@ -0,0 +1,67 @@
+// Checks what `build.rs` generates for the shaders in `src/`, no GPU needed
+use bottle_water::shader::{ENTRY_POINTS, GLSL_ENTRY_POINT, VARIANTS};
+use bottle_water::{BuiltinShader, ShaderStage};
+
+#[test]
+fn entry_points_get_their_own_modules() {
+    let blur = BuiltinShader::PostBlurFrag;
+    assert_eq!(
+        blur.entry_points(),
+        &[
+            (ShaderStage::Fragment, "blur_x"),
+            (ShaderStage::Fragment, "blur_y")
+        ]
+    );
+    // The first entry point is also what the shader's own module has, where it's called `main` like in every GLSL module
+    assert_eq!(blur.entry_point(ShaderStage::Fragment), Some("blur_x"));
+    assert_eq!(
+        blur.module_entry_point(ShaderStage::Fragment),
+        Some(GLSL_ENTRY_POINT)
+    );
+    let modules = ENTRY_POINTS
+        .iter()
+        .filter(|entry_point| entry_point.shader == blur)
+        .map(|entry_point| {
+            (
+                entry_point.name,
+                entry_point.module,
+                entry_point.module_entry_point,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        modules,
+        [
+            ("blur_x", "post/blur.frag:blur_x", "main"),
+            ("blur_y", "post/blur.frag:blur_y", "main")
+        ]
+    );
+    assert!(ENTRY_POINTS
+        .iter()
+        .all(|entry_point| !entry_point.spirv.is_empty()));
+}
+
+#[test]
+fn geometry_shaders_are_compiled_but_not_loaded() {
+    let wireframe = BuiltinShader::WireframeGeom;
+    assert_eq!(wireframe.stages(), &[ShaderStage::Geometry]);
+    assert_eq!(wireframe.entry_points(), &[(ShaderStage::Geometry, "main")]);
+    // wgpu 0.7 can't make a module out of it, so `ShaderLibrary::load_builtin` skips it
+    assert!(!wireframe.is_supported());
+    assert!(VARIANTS.iter().all(|variant| variant.shader != wireframe));
+}
+
+#[test]
+fn wgsl_keeps_its_entry_point_names() {
+    let triangle = BuiltinShader::TriangleWgsl;
+    for stage in [ShaderStage::Vertex, ShaderStage::Fragment].iter().copied() {
+        assert_eq!(
+            triangle.module_entry_point(stage),
+            triangle.entry_point(stage)
+        );
+    }
+    assert_eq!(
+        triangle.module_entry_point(ShaderStage::Vertex),
+        Some("vs_main")
+    );
+}
@@ -0,0 +1,76 @@
+// `#[derive(VertexLayout)]` against layouts worked out by hand
+use bottle_water::{Vertex, VertexLayout};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, VertexLayout)]
+#[step_mode(instance)]
+struct Instance {
+    #[location(2)]
+    model: [[f32; 4]; 4],
+    #[location(6)]
+    #[normalized]
+    color: [u8; 4],
+    // No location, but the fields after it still have to be offset past it
+    _padding: u32,
+    #[location(7)]
+    id: u32,
+}
+
+fn attribute(location: u32, offset: u64, format: wgpu::VertexFormat) -> wgpu::VertexAttribute {
+    wgpu::VertexAttribute {
+        offset,
+        shader_location: location,
+        format,
+    }
+}
+
+#[test]
+fn vertex_matches_hand_written_layout() {
+    let desc = Vertex::desc();
+    assert_eq!(desc.array_stride, 24);
+    assert_eq!(desc.step_mode, wgpu::InputStepMode::Vertex);
+    assert_eq!(
+        desc.attributes,
+        &[
+            attribute(0, 0, wgpu::VertexFormat::Float3),
+            attribute(1, 12, wgpu::VertexFormat::Float3),
+        ]
+    );
+}
+
+#[test]
+fn instance_with_matrix_and_normalized_color() {
+    let desc = Instance::desc();
+    assert_eq!(desc.array_stride, 76);
+    assert_eq!(desc.step_mode, wgpu::InputStepMode::Instance);
+    assert_eq!(
+        desc.attributes,
+        &[
+            // One location per column of the matrix
+            attribute(2, 0, wgpu::VertexFormat::Float4),
+            attribute(3, 16, wgpu::VertexFormat::Float4),
+            attribute(4, 32, wgpu::VertexFormat::Float4),
+            attribute(5, 48, wgpu::VertexFormat::Float4),
+            attribute(6, 64, wgpu::VertexFormat::Uchar4Norm),
+            attribute(7, 72, wgpu::VertexFormat::Uint),
+        ]
+    );
+}
+
+#[test]
+fn instanced_mesh_instance_layout() {
+    // The one that `shader.vert[INSTANCED]` reads, the matrix at locations 2 to 5 and the color at 6
+    let desc = bottle_water::Instance::desc();
+    assert_eq!(desc.array_stride, 80);
+    assert_eq!(desc.step_mode, wgpu::InputStepMode::Instance);
+    assert_eq!(
+        desc.attributes,
+        &[
+            attribute(2, 0, wgpu::VertexFormat::Float4),
+            attribute(3, 16, wgpu::VertexFormat::Float4),
+            attribute(4, 32, wgpu::VertexFormat::Float4),
+            attribute(5, 48, wgpu::VertexFormat::Float4),
+            attribute(6, 64, wgpu::VertexFormat::Float4),
+        ]
+    );
+}